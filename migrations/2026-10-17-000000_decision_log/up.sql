@@ -0,0 +1,16 @@
+-- Every action that was (or would have been in a dry run) taken on a post
+CREATE TABLE decisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    post_id TEXT NOT NULL,
+    status TEXT CHECK(status in ('spam', 'ham', 'unknown')) NOT NULL,
+    reason TEXT,
+    action TEXT,
+    -- The action and spam reason without any rendered text, which can change between checks of
+    -- the same post. Used to keep from repeating actions
+    action_kind TEXT,
+    spam_reason TEXT,
+    dry_run BOOLEAN NOT NULL,
+    decided_at BIGINT NOT NULL
+);
+
+create index decisions_post_id_idx on decisions (post_id);
@@ -0,0 +1,15 @@
+-- What every filter thought of every post that got checked
+CREATE TABLE verdicts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    post_id TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    status TEXT CHECK(status in ('spam', 'ham', 'unknown')) NOT NULL,
+    confidence REAL,
+    -- JSON serialized reason. Null when the filter didn't have an opinion
+    reason TEXT,
+    program_version TEXT NOT NULL,
+    config_hash TEXT NOT NULL,
+    evaluated_at BIGINT NOT NULL
+);
+
+create index verdicts_post_id_idx on verdicts (post_id);
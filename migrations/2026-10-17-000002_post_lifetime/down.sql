@@ -0,0 +1,8 @@
+ALTER TABLE posts
+DROP COLUMN removed;
+
+ALTER TABLE posts
+DROP COLUMN last_seen;
+
+ALTER TABLE posts
+DROP COLUMN first_seen;
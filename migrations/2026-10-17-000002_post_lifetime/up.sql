@@ -0,0 +1,9 @@
+-- Posts get stored as soon as they're seen and updated when they fall out of the latest posts
+ALTER TABLE posts
+ADD COLUMN first_seen BIGINT DEFAULT NULL;
+
+ALTER TABLE posts
+ADD COLUMN last_seen BIGINT DEFAULT NULL;
+
+ALTER TABLE posts
+ADD COLUMN removed BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+ALTER TABLE posts
+ADD COLUMN created REAL NOT NULL DEFAULT 0;
+
+UPDATE posts
+SET created = created_utc;
+
+ALTER TABLE posts
+DROP COLUMN created_utc;
@@ -0,0 +1,10 @@
+-- `REAL` is stored as an `f32` on our end, which can't represent current timestamps exactly.
+-- Switch to an integer column. Already stored posts can only keep what precision is left
+ALTER TABLE posts
+ADD COLUMN created_utc BIGINT NOT NULL DEFAULT 0;
+
+UPDATE posts
+SET created_utc = CAST(created AS INTEGER);
+
+ALTER TABLE posts
+DROP COLUMN created;
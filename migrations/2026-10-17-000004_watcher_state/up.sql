@@ -0,0 +1,10 @@
+-- Each watcher's live posts and debounce queues, so that restarts don't re-emit posts
+CREATE TABLE watcher_state (
+    subreddit TEXT NOT NULL,
+    queue TEXT CHECK(queue in ('live', 'fresh', 'expired')) NOT NULL,
+    position INTEGER NOT NULL,
+    post_id TEXT NOT NULL,
+    -- JSON of a live post's user reports when it's from the reports queue
+    reports TEXT,
+    PRIMARY KEY (subreddit, queue, position)
+);
@@ -0,0 +1,4 @@
+DROP INDEX posts_subreddit_idx;
+
+ALTER TABLE posts
+DROP COLUMN subreddit;
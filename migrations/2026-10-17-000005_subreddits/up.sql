@@ -0,0 +1,5 @@
+-- Multiple subreddits can be watched now. Everything before this was from r/rust
+ALTER TABLE posts
+ADD COLUMN subreddit TEXT NOT NULL DEFAULT 'rust';
+
+create index posts_subreddit_idx on posts (subreddit);
@@ -0,0 +1,284 @@
+//! Turns classified posts and comments into moderator actions on reddit
+
+use crate::{
+    config::{ActionPolicy, ActionTemplate},
+    filter::Status,
+    reddit::{self, FetchError},
+    types::{Comment, Post},
+    utils,
+};
+
+use roux::Me;
+
+// Reddit rejects reports with longer reasons
+const MAX_REPORT_REASON_LEN: usize = 100;
+
+/// A fully rendered action for a specific post
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Approve,
+    Report(String),
+    Remove,
+    Comment(String),
+    Flair {
+        text: String,
+        css_class: Option<String>,
+    },
+}
+
+impl Action {
+    /// The action without any of its rendered text
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Approve => "Approve",
+            Self::Report(_) => "Report",
+            Self::Remove => "Remove",
+            Self::Comment(_) => "Comment",
+            Self::Flair { .. } => "Flair",
+        }
+    }
+}
+
+/// Renders the actions that the policy dictates for a post with the given status
+pub fn plan(policy: &ActionPolicy, post: &Post, status: Option<&Status>) -> Vec<Action> {
+    let (templates, reason) = match status {
+        Some(Status::Spam(reason)) => {
+            let templates = policy.spam.get(reason.name()).map(Vec::as_slice);
+            (templates.unwrap_or_default(), reason.to_string())
+        }
+        // `{reason}` is only meaningful for spam
+        Some(Status::Ham(_)) => (policy.ham.as_slice(), String::new()),
+        None => return Vec::new(),
+    };
+    let render = |template: &str| {
+        template
+            .replace("{author}", &post.author)
+            .replace("{title}", &post.title)
+            .replace("{reason}", &reason)
+    };
+
+    let mut actions = Vec::new();
+    for template in templates {
+        match template {
+            ActionTemplate::Approve => actions.push(Action::Approve),
+            ActionTemplate::Report(reason) => {
+                let reason = render(reason);
+                let reason = utils::truncate_str(&reason, MAX_REPORT_REASON_LEN);
+                actions.push(Action::Report(reason.into_owned()));
+            }
+            ActionTemplate::Remove => actions.push(Action::Remove),
+            ActionTemplate::RemoveWithComment => {
+                actions.push(Action::Remove);
+                actions.push(Action::Comment(render(&policy.removal_comment)));
+            }
+            ActionTemplate::Flair { text, css_class } => actions.push(Action::Flair {
+                text: render(text),
+                css_class: css_class.clone(),
+            }),
+        }
+    }
+
+    actions
+}
+
+/// What an action gets performed on
+#[derive(Clone, Copy, Debug)]
+pub enum Target<'a> {
+    Post(&'a Post),
+    Comment(&'a Comment),
+}
+
+impl Target<'_> {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Post(post) => &post.id,
+            Self::Comment(comment) => &comment.id,
+        }
+    }
+
+    fn fullname(&self) -> String {
+        match self {
+            Self::Post(post) => post.fullname(),
+            Self::Comment(comment) => comment.fullname(),
+        }
+    }
+}
+
+pub trait Actor {
+    fn perform(&mut self, target: Target<'_>, action: &Action) -> anyhow::Result<()>;
+}
+
+/// Performs actions as the bot's (moderator) account
+pub struct RedditActor {
+    me: Me,
+}
+
+impl RedditActor {
+    pub fn new() -> anyhow::Result<Self> {
+        let me = reddit::client().login()?;
+        Ok(Self { me })
+    }
+
+    // roux doesn't cover any of the mod endpoints, so we hit them directly with its authed client
+    fn post(&self, path: &str, form: &[(&str, &str)]) -> Result<(), FetchError> {
+        let response = self
+            .me
+            .client
+            .post(format!("https://oauth.reddit.com/{path}"))
+            .form(form)
+            .send()?;
+        if let Some(error) = FetchError::from_response(&response) {
+            return Err(error);
+        }
+
+        // `api_type=json` endpoints still respond with a 200 when they turn us down
+        let body: serde_json::Value = serde_json::from_str(&response.text()?).unwrap_or_default();
+        let errors = &body["json"]["errors"];
+        if errors.as_array().is_some_and(|errors| !errors.is_empty()) {
+            return Err(FetchError::Unexpected(format!(
+                "Reddit rejected the action: {errors}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn try_perform(&self, target: Target<'_>, action: &Action) -> Result<(), FetchError> {
+        let id = target.fullname();
+        match action {
+            Action::Approve => self.post("api/approve", &[("id", &id)]),
+            Action::Report(reason) => {
+                self.post("api/report", &[("thing_id", &id), ("reason", reason)])
+            }
+            Action::Remove => self.post("api/remove", &[("id", &id), ("spam", "false")]),
+            Action::Comment(text) => self.post(
+                "api/comment",
+                &[("api_type", "json"), ("thing_id", &id), ("text", text)],
+            ),
+            Action::Flair { text, css_class } => {
+                let Target::Post(post) = target else {
+                    return Err(FetchError::Unexpected(
+                        "Only posts can be flaired".to_owned(),
+                    ));
+                };
+                self.post(
+                    &format!("r/{}/api/flair", post.subreddit),
+                    &[
+                        ("api_type", "json"),
+                        ("link", &id),
+                        ("text", text),
+                        ("css_class", css_class.as_deref().unwrap_or_default()),
+                    ],
+                )
+            }
+        }
+    }
+}
+
+impl Actor for RedditActor {
+    fn perform(&mut self, target: Target<'_>, action: &Action) -> anyhow::Result<()> {
+        tracing::info!(id = target.id(), ?action, "Performing action");
+
+        match self.try_perform(target, action) {
+            // Same as with polling, auth can start failing over time
+            Err(FetchError::Auth(error)) => {
+                tracing::info!(%error, "Attempting to refresh auth");
+                *self = Self::new()?;
+                Ok(self.try_perform(target, action)?)
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+/// Keeps track of actions instead of performing them
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingActor {
+    pub performed: Vec<(String, Action)>,
+}
+
+#[cfg(test)]
+impl Actor for RecordingActor {
+    fn perform(&mut self, target: Target<'_>, action: &Action) -> anyhow::Result<()> {
+        self.performed
+            .push((target.id().to_owned(), action.to_owned()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filter::{HamReason, SpamReason},
+        types::Origin,
+    };
+
+    #[test]
+    fn policy() {
+        let policy: ActionPolicy = toml::from_str(
+            r#"
+            ham = [{ flair = { text = "Approved" } }]
+            removal_comment = "Sorry u/{author}: {reason}"
+
+            [spam]
+            BlockedUrl = ["remove_with_comment"]
+            BlockedSnippet = [{ report = "{reason}" }]
+            "#,
+        )
+        .unwrap();
+        let post = Post {
+            link: Some("https://discord.gg/invite".to_owned()),
+            ..Post::for_test("Best base design for solos")
+        };
+
+        let mut actor = RecordingActor::default();
+        let statuses = [
+            Some(Status::Spam(SpamReason::BlockedUrl {
+                url: "https://discord.gg/invite".to_owned(),
+                origin: Origin::Link,
+            })),
+            Some(Status::Spam(SpamReason::BlockedSnippet("a".repeat(200)))),
+            Some(Status::Spam(SpamReason::UnknownYoutubeChannel {
+                channel_id: "UCunknown".to_owned(),
+                video_id: "aaaaaaaaaaa".to_owned(),
+            })),
+            Some(Status::Ham(HamReason::AllowedUrl {
+                url: "https://docs.rs".to_owned(),
+                origin: Origin::Body,
+            })),
+            None,
+        ];
+        for status in &statuses {
+            for action in plan(&policy, &post, status.as_ref()) {
+                actor.perform(Target::Post(&post), &action).unwrap();
+            }
+        }
+
+        let actions: Vec<_> = actor
+            .performed
+            .into_iter()
+            .map(|(_, action)| action)
+            .collect();
+        assert_eq!(actions.len(), 4);
+        assert_eq!(actions[0], Action::Remove);
+        assert_eq!(
+            actions[1],
+            Action::Comment(
+                "Sorry u/someone: Links to a blocked site: https://discord.gg/invite".to_owned()
+            )
+        );
+        assert!(matches!(&actions[2], Action::Report(reason) if reason.len() == 100));
+        assert_eq!(
+            actions[3],
+            Action::Flair {
+                text: "Approved".to_owned(),
+                css_class: None
+            }
+        );
+
+        let unknown_reason = toml::from_str::<ActionPolicy>("[spam]\nBlokedUrl = [\"remove\"]");
+        assert!(unknown_reason.is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -9,5 +11,27 @@ pub struct Args {
 #[derive(Subcommand)]
 pub enum Command {
     Analyze,
-    Watch,
+    /// Measure how well the filters do against the labeled posts
+    Evaluate,
+    /// Interactively categorize posts that haven't been labeled yet
+    Label,
+    /// Train the naive bayes classifier off of the labeled posts
+    Train,
+    Watch {
+        /// Log the actions that would be taken without actually doing anything on reddit
+        #[arg(long)]
+        dry_run: bool,
+        /// Feed a file of recorded listings through the filters instead of watching reddit.
+        /// Implies `--dry-run` and uses a scratch database that's thrown away afterwards
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+        /// Save every listing of each subreddit's latest posts to daily files in `DIR` that can be
+        /// replayed. The reports queue and comments aren't recorded
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+        /// Swap author and mod names in recorded listings for stand-ins that are stable for the
+        /// run, and drop other fields that identify authors like their flair
+        #[arg(long, requires = "record")]
+        redact_authors: bool,
+    },
 }
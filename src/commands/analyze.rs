@@ -1,14 +1,13 @@
-use std::cell::OnceCell;
-
 use crate::{
-    config, database,
-    filter::{self, Status},
-    types::Category,
+    config, database, filter,
+    types::{Category, StatusKind},
 };
 
 pub fn run() -> anyhow::Result<()> {
     let db = database::Database::new()?;
     let config = config::expect_config();
+    filter::init(config)?;
+    let youtube = filter::PageScraper::new();
 
     let posts = db.get_posts(Category::Lang, 10_000)?;
     let mut num_spam = 0;
@@ -18,17 +17,12 @@ pub fn run() -> anyhow::Result<()> {
     for post in &posts {
         println!("---");
         let start = std::time::Instant::now();
-        let filter_iter = filter::FilterIter::new(post, &config, &db);
-
-        let mut first_status = None;
-        for status in filter_iter.filter_map(|(_, maybe_status)| maybe_status) {
-            println!("{:?}", status);
-            first_status = first_status.or(Some(status));
-        }
-        match first_status {
-            Some(Status::Spam(_)) => num_spam += 1,
-            Some(Status::Ham(_)) => num_ham += 1,
-            None => num_unknown += 1,
+        let verdict = filter::filter(post, config, &db, &youtube);
+        println!("{verdict}");
+        match verdict.kind {
+            StatusKind::Spam => num_spam += 1,
+            StatusKind::Ham => num_ham += 1,
+            StatusKind::Unknown => num_unknown += 1,
         }
         tracing::info!("Post analysis finished in {:?}", start.elapsed());
     }
@@ -0,0 +1,240 @@
+//! Checks the filters against the labeled posts. `Lang` posts are ham and `Game` posts are spam.
+//! `Other` posts are neither, so they get their own row without counting towards precision, recall
+//! or coverage
+//!
+//! Only the newest labeled posts get scored. The classifier gets trained on everything older, so
+//! that it's never scored on posts that it learned from. Youtube channels get resolved through a
+//! cache file, so that runs over the same posts don't depend on youtube after the first one
+
+use std::{fmt, path::Path};
+
+use crate::{
+    config, database,
+    filter::{self, CachedResolver, NaiveBayesModel},
+    types::{Category, Post, StatusKind},
+    utils,
+};
+
+const MAX_POSTS_PER_CATEGORY: u32 = 100_000;
+/// The share of labeled posts that get held out for scoring
+const HELD_OUT_RATIO: f32 = 0.2;
+const YOUTUBE_CHANNELS_PATH: &str = "youtube_channels.json";
+
+pub fn run() -> anyhow::Result<()> {
+    let db = database::Database::new()?;
+    let config = config::expect_config();
+    let youtube =
+        CachedResolver::open(filter::PageScraper::new(), Path::new(YOUTUBE_CHANNELS_PATH))?;
+
+    let mut posts = Vec::new();
+    for category in [Category::Lang, Category::Game, Category::Other] {
+        posts.extend(db.get_posts(category, MAX_POSTS_PER_CATEGORY)?);
+    }
+    let (train, held_out) = split_by_age(posts);
+
+    let mut ham = Vec::new();
+    let mut spam = Vec::new();
+    for post in &train {
+        match post.category {
+            Some(Category::Lang) => ham.push(post.to_owned()),
+            Some(Category::Game) => spam.push(post.to_owned()),
+            Some(Category::Other) | None => {}
+        }
+    }
+    filter::init_naive_bayes_model(NaiveBayesModel::train(&ham, &spam));
+    println!(
+        "Trained on {} older post(s). Scoring the newest {} post(s)",
+        train.len(),
+        held_out.len()
+    );
+
+    let mut overall = Confusion::default();
+    let mut per_filter: Vec<_> = config
+        .filters
+        .enabled
+        .iter()
+        .map(|filter| (filter.name(), Confusion::default()))
+        .collect();
+    let mut misclassified = Vec::new();
+
+    for post in &held_out {
+        let Some(actual) = post.category else {
+            continue;
+        };
+        let verdict = filter::filter(post, config, &db, &youtube);
+
+        overall.add(actual, verdict.kind);
+        for (name, confusion) in &mut per_filter {
+            let predicted = verdict
+                .evidence
+                .iter()
+                .find(|(evidence_name, _)| evidence_name == name)
+                .map_or(StatusKind::Unknown, |(_, evidence)| {
+                    StatusKind::new(Some(&evidence.status))
+                });
+            confusion.add(actual, predicted);
+        }
+
+        let is_misclassified = expected_kind(actual).is_some_and(|expected| {
+            verdict.kind != StatusKind::Unknown && verdict.kind != expected
+        });
+        if is_misclassified {
+            misclassified.push((post, verdict));
+        }
+    }
+    youtube.save()?;
+
+    for (name, confusion) in &per_filter {
+        println!("--- {name}");
+        println!("{confusion}");
+    }
+    println!("=== Overall");
+    println!("{overall}");
+
+    if !misclassified.is_empty() {
+        println!("=== Misclassified");
+        for (post, verdict) in &misclassified {
+            println!(
+                "{} {:?} ({:+.2}) {}",
+                post.id,
+                verdict.kind,
+                verdict.score,
+                utils::truncate_str(&post.title, 80)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits posts into the older ones to train on and the newest ones to score
+fn split_by_age(mut posts: Vec<Post>) -> (Vec<Post>, Vec<Post>) {
+    posts.sort_by_key(|post| post.created);
+    let num_held_out = (posts.len() as f32 * HELD_OUT_RATIO).ceil() as usize;
+    let held_out = posts.split_off(posts.len() - num_held_out);
+    (posts, held_out)
+}
+
+fn expected_kind(category: Category) -> Option<StatusKind> {
+    match category {
+        Category::Lang => Some(StatusKind::Ham),
+        Category::Game => Some(StatusKind::Spam),
+        Category::Other => None,
+    }
+}
+
+/// Actual (ham, spam, other) by predicted (ham, spam, unknown) counts
+#[derive(Default)]
+struct Confusion([[u32; 3]; 3]);
+
+impl Confusion {
+    fn add(&mut self, actual: Category, predicted: StatusKind) {
+        let actual = match actual {
+            Category::Lang => 0,
+            Category::Game => 1,
+            Category::Other => 2,
+        };
+        self.0[actual][Self::predicted_index(predicted)] += 1;
+    }
+
+    fn predicted_index(kind: StatusKind) -> usize {
+        match kind {
+            StatusKind::Ham => 0,
+            StatusKind::Spam => 1,
+            StatusKind::Unknown => 2,
+        }
+    }
+
+    /// Just the ham and spam rows. `Other` posts have no right answer to score against
+    fn labeled(&self) -> &[[u32; 3]] {
+        &self.0[..2]
+    }
+
+    fn total(&self) -> u32 {
+        self.labeled().iter().flatten().sum()
+    }
+
+    /// How many posts got classified as anything other than unknown
+    fn coverage(&self) -> Option<f32> {
+        let unknown: u32 = self.labeled().iter().map(|row| row[2]).sum();
+        ratio(self.total() - unknown, self.total())
+    }
+
+    fn precision(&self, kind: StatusKind) -> Option<f32> {
+        let i = Self::predicted_index(kind);
+        let predicted: u32 = self.labeled().iter().map(|row| row[i]).sum();
+        ratio(self.0[i][i], predicted)
+    }
+
+    fn recall(&self, kind: StatusKind) -> Option<f32> {
+        let i = Self::predicted_index(kind);
+        ratio(self.0[i][i], self.0[i].iter().sum())
+    }
+}
+
+fn ratio(num: u32, denom: u32) -> Option<f32> {
+    (denom != 0).then(|| num as f32 / denom as f32)
+}
+
+impl fmt::Display for Confusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |maybe_ratio: Option<f32>| {
+            maybe_ratio.map_or_else(|| "n/a".to_owned(), |r| format!("{:.02}%", r * 100.0))
+        };
+
+        writeln!(f, "{:>12} {:>8} {:>8} {:>8}", "", "Ham", "Spam", "Unknown")?;
+        for (label, row) in ["Actual Ham", "Actual Spam", "Actual Other"]
+            .iter()
+            .zip(&self.0)
+        {
+            writeln!(f, "{label:>12} {:>8} {:>8} {:>8}", row[0], row[1], row[2])?;
+        }
+        for kind in [StatusKind::Ham, StatusKind::Spam] {
+            writeln!(
+                f,
+                "{kind:?} precision: {} recall: {}",
+                percent(self.precision(kind)),
+                percent(self.recall(kind)),
+            )?;
+        }
+        write!(f, "Coverage: {}", percent(self.coverage()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confusion() {
+        use Category::{Game, Lang, Other};
+        use StatusKind::{Ham, Spam, Unknown};
+
+        let mut confusion = Confusion::default();
+        assert_eq!(confusion.coverage(), None);
+
+        let samples = [
+            (Lang, Ham),
+            (Lang, Ham),
+            (Lang, Spam),
+            (Lang, Unknown),
+            (Game, Spam),
+            (Game, Spam),
+            (Game, Spam),
+            (Game, Ham),
+            // Other posts are shown, but don't sway any of the numbers
+            (Other, Spam),
+            (Other, Unknown),
+        ];
+        for (actual, predicted) in samples {
+            confusion.add(actual, predicted);
+        }
+
+        assert_eq!(confusion.0, [[2, 1, 1], [1, 3, 0], [0, 1, 1]]);
+        assert_eq!(confusion.coverage(), Some(7.0 / 8.0));
+        assert_eq!(confusion.precision(Spam), Some(3.0 / 4.0));
+        assert_eq!(confusion.recall(Spam), Some(3.0 / 4.0));
+        assert_eq!(confusion.precision(Ham), Some(2.0 / 3.0));
+        assert_eq!(confusion.recall(Ham), Some(2.0 / 4.0));
+    }
+}
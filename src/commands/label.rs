@@ -0,0 +1,97 @@
+//! Walks through the posts that haven't been categorized yet. Labels are written as they're made,
+//! so quitting part way through is fine
+
+use crate::{config, database, filter, types::Category, utils};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+const MAX_POSTS: u32 = 10_000;
+const BODY_PREVIEW_LEN: usize = 1_000;
+
+pub fn run() -> anyhow::Result<()> {
+    let db = database::Database::new()?;
+    let mut config = config::expect_config().clone();
+    // Labeling comes before there's a model to train, so the verdicts just go without it
+    if let Err(error) = filter::init(&config) {
+        tracing::warn!(%error, "Showing verdicts without the naive bayes classifier");
+        config
+            .filters
+            .enabled
+            .retain(|filter| filter.name() != "NaiveBayes");
+    }
+    let youtube = filter::PageScraper::new();
+
+    let posts = db.get_unlabeled_posts(MAX_POSTS)?;
+    let total = posts.len();
+    let mut num_labeled = 0;
+
+    for (i, post) in posts.iter().enumerate() {
+        println!("=== [{}/{total}] {}", i + 1, post.id);
+        println!("Title: {}", post.title);
+        println!("Author: u/{}", post.author);
+        if let Some(link) = &post.link {
+            println!("Link: {link}");
+        }
+        if let Some(body) = &post.body {
+            println!("Body:\n{}", utils::truncate_str(body, BODY_PREVIEW_LEN));
+        }
+        println!("Verdict: {}", filter::filter(post, &config, &db, &youtube));
+        println!("[l]ang [g]ame [o]ther [s]kip [q]uit");
+
+        let category = match read_choice()? {
+            Choice::Label(category) => category,
+            Choice::Skip => continue,
+            Choice::Quit => break,
+        };
+        db.set_category(&post.id, category)?;
+        num_labeled += 1;
+    }
+
+    println!("Labeled {num_labeled} post(s)");
+
+    Ok(())
+}
+
+enum Choice {
+    Label(Category),
+    Skip,
+    Quit,
+}
+
+/// Reads single keystrokes until one of them is a valid choice
+fn read_choice() -> anyhow::Result<Choice> {
+    terminal::enable_raw_mode()?;
+    let choice = loop {
+        let event = match event::read() {
+            Ok(event) => event,
+            Err(err) => break Err(err),
+        };
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        else {
+            continue;
+        };
+
+        let choice = match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => Choice::Quit,
+            KeyCode::Char('l') => Choice::Label(Category::Lang),
+            KeyCode::Char('g') => Choice::Label(Category::Game),
+            KeyCode::Char('o') => Choice::Label(Category::Other),
+            KeyCode::Char('s') => Choice::Skip,
+            KeyCode::Char('q') | KeyCode::Esc => Choice::Quit,
+            _ => continue,
+        };
+        break Ok(choice);
+    };
+    // Always try to leave the terminal how we found it
+    terminal::disable_raw_mode()?;
+
+    Ok(choice?)
+}
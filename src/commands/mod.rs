@@ -1,2 +1,5 @@
 pub mod analyze;
+pub mod evaluate;
+pub mod label;
+pub mod train;
 pub mod watch;
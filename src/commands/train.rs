@@ -0,0 +1,26 @@
+use crate::{config, database, filter::NaiveBayesModel, types::Category};
+
+const MAX_POSTS_PER_CATEGORY: u32 = 100_000;
+
+pub fn run() -> anyhow::Result<()> {
+    let db = database::Database::new()?;
+    let config = config::expect_config();
+    let model_path = &config.filters.naive_bayes.model_path;
+
+    let ham = db.get_posts(Category::Lang, MAX_POSTS_PER_CATEGORY)?;
+    let spam = db.get_posts(Category::Game, MAX_POSTS_PER_CATEGORY)?;
+    if ham.is_empty() || spam.is_empty() {
+        anyhow::bail!("Need both `Lang` and `Game` posts to train on. Try labeling some first");
+    }
+
+    let model = NaiveBayesModel::train(&ham, &spam);
+    model.save(model_path)?;
+
+    let (num_ham, num_spam) = model.num_docs();
+    println!(
+        "Trained on {num_ham} ham and {num_spam} spam post(s). Saved to {}",
+        model_path.display()
+    );
+
+    Ok(())
+}
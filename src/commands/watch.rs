@@ -1,33 +1,211 @@
-use crate::{config, database, filter, reddit};
+use crate::{
+    action::{self, Action, Actor, Target},
+    config, database,
+    filter::{self, Verdict},
+    reddit,
+    types::StatusKind,
+};
 
-use std::{thread, time::Duration};
+use std::{path::PathBuf, thread, time::Instant};
 
-const EVENT_LOOP_SLEEP_SEC: u64 = 60;
-
-pub fn run() -> anyhow::Result<()> {
-    let mut watcher = reddit::Watcher::new();
-    let db = database::Database::new()?;
+pub fn run(
+    dry_run: bool,
+    maybe_replay: Option<PathBuf>,
+    maybe_recorder: Option<reddit::Recorder>,
+) -> anyhow::Result<()> {
+    // Replays are for reproducing things offline, so they never touch reddit or the real database.
+    // They get a scratch database instead, which means authors start out without any reputation
+    let replaying = maybe_replay.is_some();
+    let db = if replaying {
+        database::Database::open(":memory:")?
+    } else {
+        database::Database::new()?
+    };
     let config = config::expect_config();
+    filter::init(config)?;
+    let youtube = filter::PageScraper::new();
+    let dry_run = dry_run || replaying;
+    // Decisions still get logged for dry runs, they just never make it to reddit
+    let mut maybe_actor = if dry_run {
+        tracing::info!("Dry run. No actions will be performed");
+        None
+    } else {
+        Some(action::RedditActor::new()?)
+    };
+
+    // Posts get checked with the config of the subreddit they're from after applying its overrides
+    let subreddit_configs: Vec<_> = config
+        .subreddits
+        .iter()
+        .map(|subreddit| (subreddit.name.as_str(), config.for_subreddit(subreddit)))
+        .collect();
+    let config_for = |subreddit: &str| {
+        subreddit_configs
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(subreddit))
+            .map_or(config, |(_, config)| config)
+    };
+
+    // Live watchers are keyed by their subreddit to save their state. Replays start from scratch
+    // and leave the saved state alone
+    let mut watchers = Vec::new();
+    let mut comment_streams = Vec::new();
+    match maybe_replay {
+        Some(replay_path) => {
+            tracing::info!(replay_path = %replay_path.display(), "Replaying recorded listings");
+            let source = reddit::ReplaySource::open(&replay_path)?;
+            let watcher =
+                reddit::Watcher::with_source(Box::new(source), Default::default(), &config.watcher);
+            watchers.push((None, watcher));
+        }
+        None => {
+            for subreddit in &config.subreddits {
+                let name = &subreddit.name;
+                let state = db.get_watcher_state(name)?;
+                let watcher =
+                    reddit::Watcher::new(name, state, &config.watcher, maybe_recorder.clone());
+                watchers.push((Some(name.to_owned()), watcher));
+
+                if subreddit.watch_reports {
+                    // Reported posts get re-checked with the reports in hand
+                    let key = format!("{name}:reports");
+                    let state = db.get_watcher_state(&key)?;
+                    let watcher = reddit::Watcher::for_reports(name, state, &config.watcher);
+                    watchers.push((Some(key), watcher));
+                }
+                if subreddit.watch_comments {
+                    let key = format!("{name}:comments");
+                    let seen = db.get_seen_comments(&key)?;
+                    let stream = reddit::CommentStream::new(name, seen, &config.watcher);
+                    comment_streams.push((key, stream));
+                }
+            }
+        }
+    }
 
     let mut num_ham = 0;
     let mut num_spam = 0;
     let mut unknown = 0;
 
     loop {
-        let reddit::Update { fresh, expired } = watcher.update();
+        for (maybe_name, watcher) in &mut watchers {
+            // Replays don't need to wait on anyone
+            if !replaying && !watcher.is_due() {
+                continue;
+            }
+
+            let reddit::Update { fresh, expired } = watcher.update();
+
+            // Store posts right away, so that nothing is lost if we go down before they expire
+            db.upsert_posts(&fresh)?;
+            db.upsert_posts(&expired)?;
+
+            for post in &fresh {
+                let config = config_for(&post.subreddit);
+                let verdict = filter::filter(post, config, &db, &youtube);
+                match verdict.kind {
+                    StatusKind::Spam => num_spam += 1,
+                    StatusKind::Ham => num_ham += 1,
+                    StatusKind::Unknown => unknown += 1,
+                }
+                tracing::info!(subreddit = %post.subreddit, num_spam, num_ham, unknown, %verdict);
+                db.insert_verdicts(post, &verdict, &config.hash)?;
+
+                let actions = action::plan(&config.actions, post, verdict.status());
+                take_actions(
+                    &db,
+                    &mut maybe_actor,
+                    Target::Post(post),
+                    &verdict,
+                    &actions,
+                    dry_run,
+                )?;
+            }
+            // Only saved once everything's been handled. Going down part way through means a post
+            // could get handled twice, but that beats never handling it at all
+            if let Some(name) = maybe_name {
+                db.save_watcher_state(name, watcher.state())?;
+            }
+        }
+
+        for (key, stream) in &mut comment_streams {
+            if !stream.is_due() {
+                continue;
+            }
 
-        db.insert_posts(expired)?;
+            for comment in stream.update() {
+                let config = config_for(&comment.subreddit);
+                // Comments are stored under their fullname, so they share the verdicts with posts
+                let post = comment.as_post();
+                let verdict = filter::filter(&post, config, &db, &youtube);
+                tracing::info!(subreddit = %comment.subreddit, comment.id = %comment.id, %verdict);
+                db.insert_verdicts(&post, &verdict, &config.hash)?;
 
-        for post in &fresh {
-            let status = filter::filter(post, &config, &db);
-            match status {
-                Some(filter::Status::Spam(_)) => num_spam += 1,
-                Some(filter::Status::Ham(_)) => num_ham += 1,
-                None => unknown += 1,
+                let actions = action::plan(&config.comment_actions, &post, verdict.status());
+                take_actions(
+                    &db,
+                    &mut maybe_actor,
+                    Target::Comment(&comment),
+                    &verdict,
+                    &actions,
+                    dry_run,
+                )?;
             }
-            tracing::info!(num_spam, num_ham, unknown, filter_result = ?status);
+            // Same as with the watchers, comments could get handled twice, but never skipped
+            db.save_seen_comments(key, stream.seen())?;
         }
 
-        thread::sleep(Duration::from_secs(EVENT_LOOP_SLEEP_SEC));
+        if watchers.iter().all(|(_, watcher)| watcher.is_done()) {
+            break;
+        }
+        if !replaying {
+            let next_poll = watchers
+                .iter()
+                .map(|(_, watcher)| watcher.next_poll())
+                .chain(comment_streams.iter().map(|(_, stream)| stream.next_poll()))
+                .min()
+                .expect("There's always at least one subreddit");
+            thread::sleep(next_poll.saturating_duration_since(Instant::now()));
+        }
     }
+
+    println!("Spam: {num_spam} Ham: {num_ham} Unknown: {unknown}");
+
+    Ok(())
+}
+
+/// Performs the planned actions and logs them. Anything that was already done when the post was
+/// last checked stays done, and failed actions are left out of the log so that a later check can
+/// retry them
+fn take_actions(
+    db: &database::Database,
+    maybe_actor: &mut Option<action::RedditActor>,
+    target: Target<'_>,
+    verdict: &Verdict,
+    actions: &[Action],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    // Comments are logged under their fullname, so that they can share the log with posts
+    let post = match target {
+        Target::Post(post) => post.to_owned(),
+        Target::Comment(comment) => comment.as_post(),
+    };
+    if actions.is_empty() {
+        db.insert_decision(&post, verdict, None, dry_run)?;
+    }
+    for action in actions {
+        if db.has_decision(&post, verdict, action, dry_run)? {
+            tracing::debug!(id = target.id(), ?action, "Action was already taken");
+            continue;
+        }
+        if let Some(actor) = maybe_actor {
+            if let Err(error) = actor.perform(target, action) {
+                tracing::warn!(%error, id = target.id(), ?action, "Failed performing action");
+                continue;
+            }
+        }
+        db.insert_decision(&post, verdict, Some(action), dry_run)?;
+    }
+
+    Ok(())
 }
@@ -1,11 +1,17 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     env, fs,
+    ops::RangeInclusive,
     path::PathBuf,
     sync::OnceLock,
 };
 
+use crate::filter::{Filter, SpamReason};
+
+use publicsuffix::{List, Psl};
+use regex::{Regex, RegexBuilder};
 use serde::{de::Error as DeError, Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
 use url::Url;
 
 static GLOBAL_SECRETS: OnceLock<Secrets> = OnceLock::new();
@@ -57,10 +63,39 @@ pub struct RedditOauth2 {
     pub password: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(rename = "url")]
     pub url_filters: UrlFilters,
+    #[serde(default)]
+    pub youtube: YoutubeFilters,
+    #[serde(default, rename = "snippet")]
+    pub snippet_filters: SnippetFilters,
+    #[serde(default)]
+    pub actions: ActionPolicy,
+    /// Comments get their own policy since they're usually dealt with more lightly than posts
+    #[serde(
+        default = "default_comment_actions",
+        deserialize_with = "deserialize_comment_actions"
+    )]
+    pub comment_actions: ActionPolicy,
+    #[serde(default)]
+    pub scoring: Scoring,
+    #[serde(default)]
+    pub filters: FilterSettings,
+    #[serde(default)]
+    pub watcher: WatcherSettings,
+    /// Subreddits to watch. Each one can override some of the settings above
+    #[serde(
+        default = "default_subreddits",
+        rename = "subreddit",
+        deserialize_with = "deserialize_subreddits"
+    )]
+    pub subreddits: Vec<SubredditConfig>,
+    /// Hash of the config file's text, so that logged verdicts can be tied back to the config that
+    /// produced them
+    #[serde(skip)]
+    pub hash: String,
 }
 
 impl Config {
@@ -71,25 +106,764 @@ impl Config {
         tracing::info!(config_path, "Reading config at path");
         let config_path = PathBuf::from(&config_path);
         let config_text = fs::read_to_string(&config_path)?;
-        let config = toml::from_str(&config_text)?;
+        let mut config: Self = toml::from_str(&config_text)?;
+        config.validate()?;
+        config.hash = format!("{:x}", Sha256::digest(&config_text));
 
         Ok(config)
     }
+
+    /// Catches values that deserialize fine, but don't make sense
+    fn validate(&self) -> anyhow::Result<()> {
+        let Scoring {
+            spam_threshold,
+            ham_threshold,
+        } = self.scoring;
+        anyhow::ensure!(
+            ham_threshold < spam_threshold,
+            "`scoring.ham_threshold` ({ham_threshold}) has to be below `scoring.spam_threshold` \
+            ({spam_threshold})"
+        );
+
+        self.filters.validate()
+    }
+
+    /// The config with a subreddit's overrides applied
+    pub fn for_subreddit(&self, subreddit: &SubredditConfig) -> Self {
+        let mut config = self.clone();
+        if let Some(url_filters) = &subreddit.url_filters {
+            config.url_filters = url_filters.clone();
+        }
+        if let Some(enabled) = &subreddit.enabled_filters {
+            config.filters.enabled = enabled.clone();
+        }
+        if let Some(actions) = &subreddit.actions {
+            config.actions = actions.clone();
+        }
+        if let Some(comment_actions) = &subreddit.comment_actions {
+            config.comment_actions = comment_actions.clone();
+        }
+
+        config
+    }
+}
+
+/// A subreddit to watch along with any settings that differ from the top-level ones
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SubredditConfig {
+    pub name: String,
+    #[serde(default, rename = "url")]
+    pub url_filters: Option<UrlFilters>,
+    #[serde(default, deserialize_with = "deserialize_maybe_enabled_filters")]
+    pub enabled_filters: Option<Vec<Filter>>,
+    #[serde(default)]
+    pub actions: Option<ActionPolicy>,
+    #[serde(default, deserialize_with = "deserialize_maybe_comment_actions")]
+    pub comment_actions: Option<ActionPolicy>,
+    /// Whether to also watch the reports queue. The bot needs to be a mod for this
+    #[serde(default)]
+    pub watch_reports: bool,
+    #[serde(default)]
+    pub watch_comments: bool,
+}
+
+impl SubredditConfig {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            url_filters: None,
+            enabled_filters: None,
+            actions: None,
+            comment_actions: None,
+            watch_reports: false,
+            watch_comments: false,
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
+fn default_subreddits() -> Vec<SubredditConfig> {
+    vec![SubredditConfig::new("rust")]
+}
+
+fn deserialize_subreddits<'de, D>(deserializer: D) -> Result<Vec<SubredditConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let subreddits = <Vec<SubredditConfig>>::deserialize(deserializer)?;
+    if subreddits.is_empty() {
+        return Err(DeError::custom("At least one subreddit is needed"));
+    }
+    for (i, subreddit) in subreddits.iter().enumerate() {
+        if subreddits[..i]
+            .iter()
+            .any(|prev| prev.name.eq_ignore_ascii_case(&subreddit.name))
+        {
+            return Err(DeError::custom(format!(
+                "Subreddit `{}` is listed more than once",
+                subreddit.name
+            )));
+        }
+    }
+
+    Ok(subreddits)
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct UrlFilters {
     pub allow: UrlSet,
     pub block: UrlSet,
 }
 
+/// Which filters run (in order) along with parameters for the filters that take them
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterSettings {
+    #[serde(deserialize_with = "deserialize_enabled_filters")]
+    pub enabled: Vec<Filter>,
+    #[serde(rename = "AllowOrBlockUrl")]
+    pub allow_or_block_url: AllowOrBlockUrlParams,
+    #[serde(rename = "AllowOrBlockSnippet")]
+    pub allow_or_block_snippet: AllowOrBlockSnippetParams,
+    #[serde(rename = "ReputableAuthor")]
+    pub reputable_author: ReputableAuthorParams,
+    #[serde(rename = "YoutubeChannel")]
+    pub youtube_channel: YoutubeChannelParams,
+    #[serde(rename = "ContainsRustCode")]
+    pub contains_rust_code: ContainsRustCodeParams,
+    #[serde(rename = "NaiveBayes")]
+    pub naive_bayes: NaiveBayesParams,
+    #[serde(rename = "GameVocabulary")]
+    pub game_vocabulary: GameVocabularyParams,
+    #[serde(rename = "TrustedReports")]
+    pub trusted_reports: TrustedReportsParams,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: Filter::defaults(),
+            allow_or_block_url: AllowOrBlockUrlParams::default(),
+            allow_or_block_snippet: AllowOrBlockSnippetParams::default(),
+            reputable_author: ReputableAuthorParams::default(),
+            youtube_channel: YoutubeChannelParams::default(),
+            contains_rust_code: ContainsRustCodeParams::default(),
+            naive_bayes: NaiveBayesParams::default(),
+            game_vocabulary: GameVocabularyParams::default(),
+            trusted_reports: TrustedReportsParams::default(),
+        }
+    }
+}
+
+impl FilterSettings {
+    fn validate(&self) -> anyhow::Result<()> {
+        let confidences = [
+            (
+                "AllowOrBlockUrl.link_confidence",
+                self.allow_or_block_url.link_confidence,
+            ),
+            (
+                "AllowOrBlockUrl.in_text_confidence",
+                self.allow_or_block_url.in_text_confidence,
+            ),
+            (
+                "AllowOrBlockSnippet.confidence",
+                self.allow_or_block_snippet.confidence,
+            ),
+            (
+                "ReputableAuthor.confidence",
+                self.reputable_author.confidence,
+            ),
+            (
+                "YoutubeChannel.known_confidence",
+                self.youtube_channel.known_confidence,
+            ),
+            (
+                "YoutubeChannel.unknown_confidence",
+                self.youtube_channel.unknown_confidence,
+            ),
+            (
+                "ContainsRustCode.fenced_confidence",
+                self.contains_rust_code.fenced_confidence,
+            ),
+            (
+                "ContainsRustCode.detected_confidence",
+                self.contains_rust_code.detected_confidence,
+            ),
+            ("TrustedReports.confidence", self.trusted_reports.confidence),
+        ];
+        for (name, confidence) in confidences {
+            ensure_within(name, confidence, 0.0..=1.0)?;
+        }
+
+        // The classifier always leans one way or the other, so anything below a coin flip is moot
+        ensure_within(
+            "NaiveBayes.min_probability",
+            self.naive_bayes.min_probability,
+            0.5..=1.0,
+        )?;
+
+        let GameVocabularyParams {
+            min_score, terms, ..
+        } = &self.game_vocabulary;
+        anyhow::ensure!(
+            *min_score > 0.0 && min_score.is_finite(),
+            "`filters.GameVocabulary.min_score` is {min_score}, but has to be positive"
+        );
+        for (term, GameTerm { weight, .. }) in terms {
+            ensure_within(&format!("GameVocabulary.terms.{term}"), *weight, 0.0..=1.0)?;
+        }
+
+        anyhow::ensure!(
+            self.trusted_reports.min_reports > 0,
+            "`filters.TrustedReports.min_reports` has to be at least 1"
+        );
+
+        Ok(())
+    }
+}
+
+fn ensure_within(name: &str, value: f32, range: RangeInclusive<f32>) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        range.contains(&value),
+        "`filters.{name}` is {value}, but has to be within {}..={}",
+        range.start(),
+        range.end()
+    );
+    Ok(())
+}
+
+fn deserialize_enabled_filters<'de, D>(deserializer: D) -> Result<Vec<Filter>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let filters = <Vec<Filter>>::deserialize(deserializer)?;
+    for (i, filter) in filters.iter().enumerate() {
+        if filters[..i].iter().any(|prev| prev.name() == filter.name()) {
+            return Err(DeError::custom(format!(
+                "Filter `{}` is enabled more than once",
+                filter.name()
+            )));
+        }
+    }
+
+    Ok(filters)
+}
+
+fn deserialize_maybe_enabled_filters<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<Filter>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_enabled_filters(deserializer).map(Some)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllowOrBlockUrlParams {
+    /// For the link of a link-post, which is what the post is about
+    pub link_confidence: f32,
+    /// For links in the title or body, which may just be an aside
+    pub in_text_confidence: f32,
+}
+
+impl Default for AllowOrBlockUrlParams {
+    fn default() -> Self {
+        Self {
+            link_confidence: 0.9,
+            in_text_confidence: 0.6,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllowOrBlockSnippetParams {
+    pub confidence: f32,
+}
+
+impl Default for AllowOrBlockSnippetParams {
+    fn default() -> Self {
+        Self { confidence: 0.7 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReputableAuthorParams {
+    /// Minimum score for a post to count towards an author's reputation
+    pub karma_threshold: u16,
+    /// Number of posts above the `karma_threshold` to be considered reputable
+    pub num_posts_threshold: u32,
+    pub confidence: f32,
+}
+
+impl Default for ReputableAuthorParams {
+    fn default() -> Self {
+        Self {
+            karma_threshold: 3,
+            num_posts_threshold: 2,
+            // Regulars can still post the odd off-topic link, so this can be outweighed
+            confidence: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct YoutubeChannelParams {
+    /// For videos from channels in either the allow or block list
+    pub known_confidence: f32,
+    /// For videos from any other channel when `block_unknown` is set
+    pub unknown_confidence: f32,
+}
+
+impl Default for YoutubeChannelParams {
+    fn default() -> Self {
+        Self {
+            known_confidence: 0.9,
+            // Plenty of on-topic videos come from channels that we just haven't seen before
+            unknown_confidence: 0.4,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContainsRustCodeParams {
+    /// For code blocks that are fenced with a language
+    pub fenced_confidence: f32,
+    /// For code that the heuristics pick out as Rust
+    pub detected_confidence: f32,
+}
+
+impl Default for ContainsRustCodeParams {
+    fn default() -> Self {
+        Self {
+            fenced_confidence: 0.8,
+            detected_confidence: 0.7,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NaiveBayesParams {
+    /// Where the `train` command saves the model to and the filter loads it from
+    pub model_path: PathBuf,
+    /// Posts only get classified when the model is at least this sure
+    pub min_probability: f32,
+}
+
+impl Default for NaiveBayesParams {
+    fn default() -> Self {
+        Self {
+            model_path: PathBuf::from("naive_bayes_model.json"),
+            min_probability: 0.9,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameVocabularyParams {
+    /// Minimum summed weight of all the matched terms to consider a post spam
+    pub min_score: f32,
+    /// How many words on either side of a term to look for its guards
+    pub guard_window: usize,
+    /// Terms (which can be multiple words) and their weights
+    pub terms: BTreeMap<String, GameTerm>,
+}
+
+impl Default for GameVocabularyParams {
+    fn default() -> Self {
+        // Just a handful of terms that are hard to mistake for programming talk. The full lexicon
+        // lives in the config
+        let terms = [
+            ("looking for group", 0.5),
+            ("low pop", 0.4),
+            ("monument", 0.4),
+            ("sulfur", 0.5),
+        ];
+
+        Self {
+            min_score: 0.6,
+            guard_window: 5,
+            terms: terms
+                .into_iter()
+                .map(|(term, weight)| {
+                    let game_term = GameTerm {
+                        weight,
+                        unless: Vec::new(),
+                    };
+                    (term.to_owned(), game_term)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Either just a weight, or a table with a `weight` and the words that guard against the term
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "RawGameTerm")]
+pub struct GameTerm {
+    pub weight: f32,
+    pub unless: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawGameTerm {
+    Weight(f32),
+    Guarded {
+        weight: f32,
+        #[serde(default)]
+        unless: Vec<String>,
+    },
+}
+
+impl From<RawGameTerm> for GameTerm {
+    fn from(raw: RawGameTerm) -> Self {
+        match raw {
+            RawGameTerm::Weight(weight) => Self {
+                weight,
+                unless: Vec::new(),
+            },
+            RawGameTerm::Guarded { weight, unless } => Self {
+                weight,
+                unless: unless.iter().map(|word| word.to_lowercase()).collect(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrustedReportsParams {
+    /// Report reasons that mean a post is about the game. Matched case-insensitively anywhere in
+    /// the reason
+    pub reasons: Vec<String>,
+    /// How many matching reports it takes before they're trusted
+    pub min_reports: u32,
+    pub confidence: f32,
+}
+
+impl Default for TrustedReportsParams {
+    fn default() -> Self {
+        Self {
+            reasons: ["about the game", "rust the game", "playrust"]
+                .map(ToOwned::to_owned)
+                .to_vec(),
+            min_reports: 2,
+            confidence: 0.4,
+        }
+    }
+}
+
+/// How the latest posts get fetched from reddit
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatcherSettings {
+    /// How many pages to go back through looking for posts we've already seen. Bursts of posts
+    /// beyond that are missed
+    pub max_pages: u32,
+    /// How long to wait between polls when everything's going fine
+    pub poll_interval_sec: u64,
+    /// The longest to wait after failures in a row
+    pub max_backoff_sec: u64,
+}
+
+impl Default for WatcherSettings {
+    fn default() -> Self {
+        Self {
+            max_pages: 5,
+            poll_interval_sec: 60,
+            max_backoff_sec: 30 * 60,
+        }
+    }
+}
+
+/// Thresholds for the summed score of all filters' evidence. Spam scores positive and ham scores
+/// negative, so anything between the two thresholds is left as unknown
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scoring {
+    pub spam_threshold: f32,
+    pub ham_threshold: f32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            spam_threshold: 0.5,
+            ham_threshold: -0.5,
+        }
+    }
+}
+
+/// What to do with posts based on how they were classified
+///
+/// Posts that are neither ham nor spam are always left alone
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActionPolicy {
+    #[serde(default)]
+    pub ham: Vec<ActionTemplate>,
+    /// Keyed by the name of the `SpamReason` variant
+    #[serde(default, deserialize_with = "deserialize_spam_actions")]
+    pub spam: BTreeMap<String, Vec<ActionTemplate>>,
+    /// Supports `{author}`, `{title}`, and `{reason}` placeholders
+    #[serde(default = "default_removal_comment")]
+    pub removal_comment: String,
+}
+
+impl Default for ActionPolicy {
+    fn default() -> Self {
+        Self {
+            ham: Vec::new(),
+            spam: BTreeMap::new(),
+            removal_comment: default_removal_comment(),
+        }
+    }
+}
+
+/// The default removal comment for both posts and comments. `{kind}` gets filled in with which one
+/// it's for
+const REMOVAL_COMMENT_TEMPLATE: &str =
+    "Hi u/{author}, your {kind} has been removed because it looks \
+    like it's about Rust the game. This subreddit is for the Rust programming language. You're \
+    probably looking for r/playrust instead.\n\n\
+    *I am a bot, and this action was performed automatically. Please message the moderators if you \
+    think this was a mistake.*";
+
+fn default_removal_comment() -> String {
+    REMOVAL_COMMENT_TEMPLATE.replace("{kind}", "post")
+}
+
+fn default_comment_removal_comment() -> String {
+    REMOVAL_COMMENT_TEMPLATE.replace("{kind}", "comment")
+}
+
+fn default_comment_actions() -> ActionPolicy {
+    ActionPolicy {
+        removal_comment: default_comment_removal_comment(),
+        ..ActionPolicy::default()
+    }
+}
+
+/// Same as a post's `ActionPolicy`, except comments can't be flaired and get removed with a
+/// comment-specific message by default
+fn deserialize_comment_actions<'de, D>(deserializer: D) -> Result<ActionPolicy, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct CommentPolicy {
+        #[serde(default)]
+        ham: Vec<ActionTemplate>,
+        #[serde(default, deserialize_with = "deserialize_spam_actions")]
+        spam: BTreeMap<String, Vec<ActionTemplate>>,
+        #[serde(default = "default_comment_removal_comment")]
+        removal_comment: String,
+    }
+
+    let CommentPolicy {
+        ham,
+        spam,
+        removal_comment,
+    } = CommentPolicy::deserialize(deserializer)?;
+    if ham
+        .iter()
+        .chain(spam.values().flatten())
+        .any(|template| matches!(template, ActionTemplate::Flair { .. }))
+    {
+        return Err(DeError::custom("Comments can't be flaired"));
+    }
+
+    Ok(ActionPolicy {
+        ham,
+        spam,
+        removal_comment,
+    })
+}
+
+fn deserialize_maybe_comment_actions<'de, D>(
+    deserializer: D,
+) -> Result<Option<ActionPolicy>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_comment_actions(deserializer).map(Some)
+}
+
+fn deserialize_spam_actions<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Vec<ActionTemplate>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let spam = <BTreeMap<String, Vec<ActionTemplate>>>::deserialize(deserializer)?;
+    match spam
+        .keys()
+        .find(|name| !SpamReason::NAMES.contains(&name.as_str()))
+    {
+        Some(name) => Err(DeError::custom(format!(
+            "Unknown spam reason `{name}`. Expected one of {:?}",
+            SpamReason::NAMES
+        ))),
+        None => Ok(spam),
+    }
+}
+
+/// An action as written in the config. `{reason}` and friends get filled in per-post
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionTemplate {
+    Approve,
+    Report(String),
+    Remove,
+    RemoveWithComment,
+    Flair {
+        text: String,
+        css_class: Option<String>,
+    },
+}
+
+/// Youtube channel IDs (the `UC...` ones) to allow or block videos from
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct YoutubeFilters {
+    #[serde(default)]
+    pub allow: BTreeSet<String>,
+    #[serde(default)]
+    pub block: BTreeSet<String>,
+    /// Treat videos from channels that aren't in either list as spam
+    #[serde(default)]
+    pub block_unknown: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SnippetFilters {
+    #[serde(default)]
+    pub allow: SnippetSet,
+    #[serde(default)]
+    pub block: SnippetSet,
+}
+
+/// A list of phrases and regexes to match against a post's text
+#[derive(Debug, Clone, Default)]
+pub struct SnippetSet(Vec<Regex>);
+
+impl SnippetSet {
+    /// Returns the first piece of text matched by any of the snippets
+    pub fn find<'text>(&self, text: &'text str) -> Option<&'text str> {
+        self.0
+            .iter()
+            .find_map(|snippet| snippet.find(text))
+            .map(|found| found.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SnippetSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let snippets = <Vec<SnippetNeedle>>::deserialize(deserializer)?;
+        let regexes = snippets
+            .into_iter()
+            .map(SnippetNeedle::compile)
+            .collect::<anyhow::Result<_>>()
+            .map_err(DeError::custom)?;
+        Ok(Self(regexes))
+    }
+}
+
+/// Either a plain phrase, or a table with either a `phrase` or `regex` along with its options
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnippetNeedle {
+    Phrase(String),
+    Detailed(SnippetOptions),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnippetOptions {
+    phrase: Option<String>,
+    regex: Option<String>,
+    #[serde(default = "default_true")]
+    case_insensitive: bool,
+    #[serde(default = "default_true")]
+    word_boundary: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl SnippetNeedle {
+    fn compile(self) -> anyhow::Result<Regex> {
+        let SnippetOptions {
+            phrase,
+            regex,
+            case_insensitive,
+            word_boundary,
+        } = match self {
+            Self::Phrase(phrase) => SnippetOptions {
+                phrase: Some(phrase),
+                regex: None,
+                case_insensitive: true,
+                word_boundary: true,
+            },
+            Self::Detailed(options) => options,
+        };
+
+        let pattern = match (phrase, regex) {
+            // Let phrases match across any kind and amount of whitespace
+            (Some(phrase), None) => phrase
+                .split_whitespace()
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(r"\s+"),
+            (None, Some(regex)) => regex,
+            _ => anyhow::bail!("Snippets need exactly one of `phrase` or `regex`"),
+        };
+        let pattern = if word_boundary {
+            format!(r"\b(?:{pattern})\b")
+        } else {
+            pattern
+        };
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        Ok(regex)
+    }
+}
+
+/// The public suffix list that's bundled in at build time. Refresh it from
+/// <https://publicsuffix.org/list/public_suffix_list.dat> every now and then
+static PUBLIC_SUFFIXES: OnceLock<List> = OnceLock::new();
+
+fn public_suffixes() -> &'static List {
+    PUBLIC_SUFFIXES.get_or_init(|| {
+        include_str!("../public_suffix_list.dat")
+            .parse()
+            .expect("The bundled public suffix list is valid")
+    })
+}
+
 /// A set formed from Url's domains
 ///
-/// Maps TLDs -> domains -> optional sub-domains
+/// Maps public suffixes -> domains -> optional sub-domains, where a missing set of domains means
+/// every domain under the public suffix is included
 ///
 /// This complex structure is just to avoid a linear scan over all the domains
-#[derive(Debug, Default)]
-pub struct UrlSet(BTreeMap<String, BTreeMap<String, Option<BTreeSet<String>>>>);
+#[derive(Debug, Clone, Default)]
+pub struct UrlSet(BTreeMap<String, Option<UrlDomains>>);
+
+/// Domains -> optional sub-domains, where a missing set of sub-domains means all of them
+type UrlDomains = BTreeMap<String, Option<BTreeSet<String>>>;
 
 impl UrlSet {
     fn new(needles: Vec<UrlNeedle>) -> Self {
@@ -97,11 +871,18 @@ impl UrlSet {
 
         for UrlNeedle {
             maybe_sub,
-            base,
+            maybe_base,
             top,
         } in needles
         {
-            let domain_set = inner.entry(top).or_default();
+            let Some(base) = maybe_base else {
+                // Wildcards take over the whole public suffix
+                inner.insert(top, None);
+                continue;
+            };
+            let Some(domain_set) = inner.entry(top).or_insert_with(|| Some(BTreeMap::new())) else {
+                continue;
+            };
 
             if let Some(sub) = maybe_sub {
                 if let Some(subs) = domain_set
@@ -122,22 +903,24 @@ impl UrlSet {
     pub fn contains(&self, url: &Url) -> bool {
         fn inner(set: &UrlSet, url: &Url) -> Option<bool> {
             let domain = url.domain()?;
-            let UrlNeedle {
-                maybe_sub,
-                base,
-                top,
-            } = UrlNeedle::new(domain).ok()?;
+            let (top, base, maybe_sub) = split_domain(domain)?;
 
-            let bases = set.0.get(&top)?;
-            let maybe_subs = bases.get(&base)?;
+            let Some(bases) = set.0.get(top)? else {
+                // Filter allows for every domain under the suffix
+                return Some(true);
+            };
+            let maybe_subs = bases.get(base)?;
 
             let contains = match (maybe_subs, maybe_sub) {
                 // Filter allows for all subdomains
                 (None, _) => true,
                 // Filter is only for specific subdomains
                 (Some(_), None) => false,
-                // Check subdomain match
-                (Some(subs), Some(sub)) => subs.contains(&sub),
+                // Check if the subdomain or any of its parents match
+                (Some(subs), Some(sub)) => std::iter::successors(Some(sub), |sub| {
+                    sub.split_once('.').map(|(_, rest)| rest)
+                })
+                .any(|sub| subs.contains(sub)),
             };
 
             Some(contains)
@@ -157,28 +940,64 @@ impl<'de> Deserialize<'de> for UrlSet {
     }
 }
 
+/// Splits a domain into its public suffix, the label right before it, and whatever's left over
+///
+/// `docs.foo.example.co.uk` gets split into `co.uk`, `example`, and `docs.foo`. Domains that are
+/// just a public suffix have nothing to split off
+fn split_domain(domain: &str) -> Option<(&str, &str, Option<&str>)> {
+    let domain = domain.trim_end_matches('.');
+    let registrable = public_suffixes().domain(domain.as_bytes())?;
+    let top_len = registrable.suffix().as_bytes().len();
+
+    let (rest, top) = domain.split_at(domain.len() - top_len);
+    let rest = rest.strip_suffix('.')?;
+    let (maybe_sub, base) = match rest.rsplit_once('.') {
+        Some((sub, base)) => (Some(sub), base),
+        None => (None, rest),
+    };
+
+    Some((top, base, maybe_sub))
+}
+
+/// Either a domain like `bbc.co.uk` or `docs.example.com`, or a wildcard over a public suffix like
+/// `*.github.io`
 struct UrlNeedle {
     maybe_sub: Option<String>,
-    base: String,
+    maybe_base: Option<String>,
     top: String,
 }
 
 impl UrlNeedle {
     fn new(domain: &str) -> anyhow::Result<Self> {
-        let mut pieces = domain.split('.');
-
-        let top = pieces.next_back();
-        let base = pieces.next_back();
-        let maybe_sub = pieces.next().map(ToOwned::to_owned);
-        let (base, top) = match (base, top, pieces.next()) {
-            (Some(base), Some(top), None) => (base.to_owned(), top.to_owned()),
-            _ => anyhow::bail!("Unrecognized domain format"),
+        let domain = domain.to_ascii_lowercase();
+
+        if let Some(suffix) = domain.strip_prefix("*.") {
+            let is_suffix = public_suffixes()
+                .suffix(suffix.as_bytes())
+                .is_some_and(|found| found == suffix);
+            anyhow::ensure!(
+                is_suffix,
+                "Wildcards only work over public suffixes like `*.github.io`, but `{suffix}` isn't \
+                one. Plain domains already include all of their subdomains"
+            );
+            return Ok(Self {
+                maybe_sub: None,
+                maybe_base: None,
+                top: suffix.to_owned(),
+            });
+        }
+
+        let Some((top, base, maybe_sub)) = split_domain(&domain) else {
+            anyhow::bail!(
+                "`{domain}` is a public suffix, so it isn't any one site. Use `*.{domain}` to \
+                include every site under it"
+            );
         };
 
         Ok(Self {
-            maybe_sub,
-            base,
-            top,
+            maybe_sub: maybe_sub.map(ToOwned::to_owned),
+            maybe_base: Some(base.to_owned()),
+            top: top.to_owned(),
         })
     }
 }
@@ -189,7 +1008,7 @@ impl<'de> Deserialize<'de> for UrlNeedle {
         D: Deserializer<'de>,
     {
         let domain = String::deserialize(deserializer)?;
-        UrlNeedle::new(&domain).map_err(|err| DeError::custom(err))
+        UrlNeedle::new(&domain).map_err(DeError::custom)
     }
 }
 
@@ -208,6 +1027,10 @@ mod tests {
             "b.sub.allow",
             "overrides-subdomain.allow",
             "override-me.overrides-subdomain.allow",
+            "bbc.co.uk",
+            "docs.foo.example.com",
+            "*.github.io",
+            "overridden.github.io",
         ]
         block = ["sub-less.block"]
         "#;
@@ -217,12 +1040,18 @@ mod tests {
 
         let Config {
             url_filters: UrlFilters { allow, .. },
+            ..
         } = config;
 
         let contains = [
             "https://sub-less.allow",
             "http://with-sub.sub-less.allow",
             "http://a.sub.allow",
+            "https://www.bbc.co.uk/news",
+            "https://docs.foo.example.com",
+            "https://nested.docs.foo.example.com",
+            "https://user.github.io/project",
+            "https://nested.user.github.io",
         ];
         for url_str in contains {
             let url = Url::parse(url_str).unwrap();
@@ -233,10 +1062,215 @@ mod tests {
             "https://sub.allow",
             "https://c.sub.allow",
             "http://something.random",
+            "https://co.uk",
+            "https://bbc.com",
+            "https://example.com",
+            "https://foo.example.com",
+            "https://github.io",
         ];
         for url_str in no_contains {
             let url = Url::parse(url_str).unwrap();
             assert!(!allow.contains(&url), "Url: {url_str}");
         }
+
+        // Public suffixes don't belong to any one site, and wildcards are only for them
+        for domain in ["github.io", "co.uk", "*.example.com"] {
+            assert!(UrlNeedle::new(domain).is_err(), "Domain: {domain}");
+        }
+    }
+
+    #[test]
+    fn snippets() {
+        let sample_config = r#"
+        [url]
+        allow = []
+        block = []
+
+        [snippet]
+        block = [
+            "looking for a team",
+            { phrase = "AK", case_insensitive = false },
+            { regex = "wipe(s|d)? ?day", word_boundary = false },
+        ]
+        "#;
+
+        let config: Config = toml::from_str(sample_config).unwrap();
+        let block = config.snippet_filters.block;
+
+        let matches = [
+            ("Anyone LOOKING FOR A\nteam?", "LOOKING FOR A\nteam"),
+            ("Best AK spray pattern", "AK"),
+            ("Server wipesday tomorrow", "wipesday"),
+        ];
+        for (text, expected) in matches {
+            assert_eq!(block.find(text), Some(expected), "Text: {text}");
+        }
+
+        let no_matches = ["looking for a teammate", "Spawn a task", "Blocking on IO"];
+        for text in no_matches {
+            assert_eq!(block.find(text), None, "Text: {text}");
+        }
+
+        let bad_config = r#"
+        [url]
+        allow = []
+        block = []
+
+        [snippet]
+        block = [{ phrase = "both", regex = "both" }]
+        "#;
+        assert!(toml::from_str::<Config>(bad_config).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let with_filters = |filters: &str| {
+            let config = format!("[url]\nallow = []\nblock = []\n\n[filters]\n{filters}");
+            toml::from_str::<Config>(&config).map(|config| config.filters)
+        };
+
+        let filters = with_filters(
+            r#"
+            enabled = ["ContainsRustCode", "ReputableAuthor"]
+            ReputableAuthor = { karma_threshold = 10 }
+            "#,
+        )
+        .unwrap();
+        let names: Vec<_> = filters.enabled.iter().map(Filter::name).collect();
+        assert_eq!(names, ["ContainsRustCode", "ReputableAuthor"]);
+        assert_eq!(filters.reputable_author.karma_threshold, 10);
+        assert_eq!(filters.reputable_author.num_posts_threshold, 2);
+
+        let errors = [
+            // Unknown filter
+            r#"enabled = ["ContainsRustCode", "Nonexistent"]"#,
+            // Duplicate filter
+            r#"enabled = ["ContainsRustCode", "ContainsRustCode"]"#,
+            // Params for an unknown filter
+            r#"Nonexistent = { karma_threshold = 10 }"#,
+            // Unknown param
+            r#"ReputableAuthor = { karma = 10 }"#,
+            // Wrong type for param
+            r#"ReputableAuthor = { karma_threshold = -1 }"#,
+        ];
+        for filters in errors {
+            assert!(with_filters(filters).is_err(), "Filters: {filters}");
+        }
+    }
+
+    #[test]
+    fn validation() {
+        let with_settings = |settings: &str| {
+            let config = format!("[url]\nallow = []\nblock = []\n\n{settings}");
+            toml::from_str::<Config>(&config).unwrap().validate()
+        };
+
+        with_settings("").unwrap();
+        let invalid = [
+            "[scoring]\nspam_threshold = -0.5\nham_threshold = 0.5",
+            "[filters]\nReputableAuthor = { confidence = 1.5 }",
+            "[filters]\nAllowOrBlockUrl = { link_confidence = -0.1 }",
+            "[filters]\nNaiveBayes = { min_probability = 0.2 }",
+            "[filters]\nGameVocabulary = { min_score = 0.0 }",
+            "[filters.GameVocabulary.terms]\nraid = 2.0",
+            "[filters]\nTrustedReports = { min_reports = 0 }",
+        ];
+        for settings in invalid {
+            assert!(with_settings(settings).is_err(), "Settings: {settings}");
+        }
+
+        // Typos don't quietly fall back to the defaults
+        let config = "[url]\nallow = []\nblock = []\n\n[scoring]\nspam_treshold = 0.7";
+        assert!(toml::from_str::<Config>(config).is_err());
+    }
+
+    #[test]
+    fn comment_actions() {
+        let with_settings = |settings: &str| {
+            let config = format!("[url]\nallow = []\nblock = []\n\n{settings}");
+            toml::from_str::<Config>(&config)
+        };
+
+        // Comments get their own removal comment, whether the policy is set or not
+        let config = with_settings("").unwrap();
+        assert!(config
+            .comment_actions
+            .removal_comment
+            .contains("your comment"));
+        let config = with_settings("[comment_actions]\nham = [\"approve\"]").unwrap();
+        assert!(config
+            .comment_actions
+            .removal_comment
+            .contains("your comment"));
+        assert!(config.actions.removal_comment.contains("your post"));
+
+        let flaired = [
+            "[comment_actions]\nham = [{ flair = { text = \"Approved\" } }]",
+            "[[subreddit]]\nname = \"rust\"\n[subreddit.comment_actions.spam]\n\
+            BlockedUrl = [{ flair = { text = \"Spam\" } }]",
+        ];
+        for settings in flaired {
+            assert!(with_settings(settings).is_err(), "Settings: {settings}");
+        }
+    }
+
+    #[test]
+    fn subreddits() {
+        let with_subreddits = |subreddits: &str| {
+            let config = format!(
+                "[url]\nallow = [\"docs.rs\"]\nblock = []\n\n[actions]\nham = [\"approve\"]\n\n{subreddits}"
+            );
+            toml::from_str::<Config>(&config)
+        };
+
+        // Defaults to just r/rust
+        let config = with_subreddits("").unwrap();
+        let names: Vec<_> = config.subreddits.iter().map(|sub| &sub.name).collect();
+        assert_eq!(names, ["rust"]);
+
+        let config = with_subreddits(
+            r#"
+            [[subreddit]]
+            name = "rust"
+
+            [[subreddit]]
+            name = "learnrust"
+            enabled_filters = ["ContainsRustCode"]
+            actions = { ham = [] }
+            comment_actions = { ham = ["approve"] }
+            url = { allow = [], block = ["docs.rs"] }
+            "#,
+        )
+        .unwrap();
+        let docs = Url::parse("https://docs.rs").unwrap();
+
+        let rust = config.for_subreddit(&config.subreddits[0]);
+        assert!(rust.url_filters.allow.contains(&docs));
+        assert_eq!(rust.filters.enabled.len(), Filter::defaults().len());
+        assert_eq!(rust.actions.ham, [ActionTemplate::Approve]);
+
+        let learnrust = config.for_subreddit(&config.subreddits[1]);
+        assert!(!learnrust.url_filters.allow.contains(&docs));
+        assert!(learnrust.url_filters.block.contains(&docs));
+        let names: Vec<_> = learnrust.filters.enabled.iter().map(Filter::name).collect();
+        assert_eq!(names, ["ContainsRustCode"]);
+        assert!(learnrust.actions.ham.is_empty());
+        assert_eq!(learnrust.comment_actions.ham, [ActionTemplate::Approve]);
+        assert!(rust.comment_actions.ham.is_empty());
+
+        let errors = [
+            // Duplicate subreddit
+            "[[subreddit]]\nname = \"rust\"\n[[subreddit]]\nname = \"Rust\"",
+            // Unknown override
+            "[[subreddit]]\nname = \"rust\"\nscoring = { spam_threshold = 1.0 }",
+            // Unknown filter
+            "[[subreddit]]\nname = \"rust\"\nenabled_filters = [\"Nonexistent\"]",
+        ];
+        for subreddits in errors {
+            assert!(
+                with_subreddits(subreddits).is_err(),
+                "Subreddits: {subreddits}"
+            );
+        }
     }
 }
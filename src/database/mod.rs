@@ -1,17 +1,38 @@
-use std::{env, fs, path::Path};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    env, fs,
+    path::Path,
+};
 
-use crate::types::{Category, Post};
+use crate::{
+    action::Action,
+    filter::{Status, Verdict},
+    reddit::WatcherState,
+    types::{Category, Post, StatusKind},
+};
 
 use diesel::{dsl::count, prelude::*, SqliteConnection};
+use smartstring::alias::String as SmallString;
 
+// diesel 1.x's derives trip this lint on newer compilers
+#[allow(non_local_definitions)]
 mod models;
+#[allow(non_local_definitions)]
 mod schema;
 
-use models::Post as DbPost;
-use schema::posts::{dsl as posts_dsl, table as posts_table};
+use models::{NewDecision, NewVerdict, Post as DbPost, WatcherEntry, WatcherQueue};
+use schema::{
+    decisions::{dsl as decisions_dsl, table as decisions_table},
+    posts::{dsl as posts_dsl, table as posts_table},
+    verdicts::table as verdicts_table,
+    watcher_state::{dsl as watcher_state_dsl, table as watcher_state_table},
+};
+use time::OffsetDateTime;
 
 embed_migrations!("./migrations");
 
+const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub struct Database {
     conn: SqliteConnection,
 }
@@ -25,25 +46,267 @@ impl Database {
         let db_path = Path::new(&db_url);
         fs::create_dir_all(db_path.parent().expect("db must have a folder"))?;
 
-        let conn = SqliteConnection::establish(&db_url)?;
+        Self::open(&db_url)
+    }
+
+    /// Connects to the database at `db_url` (which can be `:memory:`) and runs any migrations
+    pub fn open(db_url: &str) -> anyhow::Result<Self> {
+        let conn = SqliteConnection::establish(db_url)?;
         embedded_migrations::run(&conn)?;
 
         Ok(Self { conn })
     }
 
-    pub fn insert_posts(&self, posts: Vec<Post>) -> anyhow::Result<()> {
-        let posts: Vec<_> = posts.into_iter().map(DbPost::from).collect();
+    /// Inserts posts that haven't been seen before and refreshes the score, removal state and
+    /// last-seen time of all of them
+    pub fn upsert_posts(&self, posts: &[Post]) -> anyhow::Result<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        // diesel 1.x has no upsert for sqlite, so insert what's missing and then update everything.
+        // The same post can show up multiple times from weird combinations of posts being
+        // removed/reapproved, so the first-seen time is only ever set on insert
+        self.conn.transaction::<_, anyhow::Error, _>(|| {
+            for post in posts {
+                let post = DbPost {
+                    first_seen: Some(now),
+                    last_seen: Some(now),
+                    ..DbPost::from(post.to_owned())
+                };
+                diesel::insert_or_ignore_into(posts_table)
+                    .values(&post)
+                    .execute(&self.conn)?;
+                diesel::update(posts_dsl::posts.find(&post.id))
+                    .set((
+                        posts_dsl::score.eq(post.score),
+                        posts_dsl::removed.eq(post.removed),
+                        posts_dsl::last_seen.eq(now),
+                    ))
+                    .execute(&self.conn)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Logs an action that was taken on a post, or would have been taken for a dry run. Posts that
+    /// don't warrant any action get logged with no action
+    pub fn insert_decision(
+        &self,
+        post: &Post,
+        verdict: &Verdict,
+        maybe_action: Option<&Action>,
+        dry_run: bool,
+    ) -> anyhow::Result<()> {
+        // Keep every reason, so that we can tell what tipped the scales later
+        let reasons: Vec<_> = verdict
+            .evidence
+            .iter()
+            .map(|(name, evidence)| {
+                format!("{:+.2} {name}: {:?}", evidence.score(), evidence.status)
+            })
+            .collect();
+        let decision = NewDecision {
+            post_id: post.id.to_string(),
+            status: verdict.kind,
+            reason: (!reasons.is_empty()).then(|| reasons.join("\n")),
+            action: maybe_action.map(|action| format!("{action:?}")),
+            action_kind: maybe_action.map(|action| action.kind().to_owned()),
+            spam_reason: spam_reason_name(verdict).map(ToOwned::to_owned),
+            dry_run,
+            decided_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+
+        diesel::insert_into(decisions_table)
+            .values(&decision)
+            .execute(&self.conn)?;
+
+        Ok(())
+    }
+
+    /// Whether the action was already logged for the post for the same spam reason. Posts can get
+    /// re-checked, and any actions that were taken the first time around shouldn't be repeated.
+    /// Rendered text like a report's reason can change between checks, so only the action's kind is
+    /// compared
+    pub fn has_decision(
+        &self,
+        post: &Post,
+        verdict: &Verdict,
+        action: &Action,
+        dry_run: bool,
+    ) -> anyhow::Result<bool> {
+        let mut query = decisions_dsl::decisions
+            .filter(decisions_dsl::post_id.eq(post.id.as_str()))
+            .filter(decisions_dsl::action_kind.eq(action.kind()))
+            .filter(decisions_dsl::dry_run.eq(dry_run))
+            .into_boxed();
+        query = match spam_reason_name(verdict) {
+            Some(name) => query.filter(decisions_dsl::spam_reason.eq(name)),
+            None => query.filter(decisions_dsl::spam_reason.is_null()),
+        };
+        let num_decisions: i64 = query.select(count(decisions_dsl::id)).first(&self.conn)?;
+        Ok(num_decisions > 0)
+    }
+
+    /// Logs what each filter that ran thought of a post, including the ones that had no opinion
+    pub fn insert_verdicts(
+        &self,
+        post: &Post,
+        verdict: &Verdict,
+        config_hash: &str,
+    ) -> anyhow::Result<()> {
+        let evaluated_at = OffsetDateTime::now_utc().unix_timestamp();
+        let new_verdict = |filter: &str, status, confidence, reason| NewVerdict {
+            post_id: post.id.to_string(),
+            filter: filter.to_owned(),
+            status,
+            confidence,
+            reason,
+            program_version: PROGRAM_VERSION.to_owned(),
+            config_hash: config_hash.to_owned(),
+            evaluated_at,
+        };
+
+        let mut verdicts = Vec::new();
+        for (name, evidence) in &verdict.evidence {
+            let reason = match &evidence.status {
+                Status::Spam(reason) => serde_json::to_string(reason)?,
+                Status::Ham(reason) => serde_json::to_string(reason)?,
+            };
+            let status = StatusKind::new(Some(&evidence.status));
+            verdicts.push(new_verdict(
+                name,
+                status,
+                Some(evidence.confidence),
+                Some(reason),
+            ));
+        }
+        for name in &verdict.abstained {
+            verdicts.push(new_verdict(name, StatusKind::Unknown, None, None));
+        }
 
-        // Ignore existing entries, because weird combinations of posts being removed/reapproved
-        // with the program stopping and starting (which clears the debounce queue) could cause the
-        // same post to be added multiple times
-        diesel::insert_or_ignore_into(posts_table)
-            .values(&posts)
+        diesel::insert_into(verdicts_table)
+            .values(&verdicts)
             .execute(&self.conn)?;
 
         Ok(())
     }
 
+    /// Replaces the stored state for a subreddit's watcher. Live posts are stored by id, so they
+    /// need to have been inserted already
+    ///
+    /// The reports queue's watcher is keyed with a `:reports` suffix on the subreddit. Its live
+    /// posts keep their reports, so that restarts don't make them look freshly reported
+    pub fn save_watcher_state(&self, subreddit: &str, state: &WatcherState) -> anyhow::Result<()> {
+        let WatcherState {
+            live,
+            fresh_debounce,
+            expired_debounce,
+        } = state;
+        let mut live_entries = Vec::new();
+        for post in live {
+            let reports = if post.reports.is_empty() {
+                None
+            } else {
+                Some(serde_json::to_string(&post.reports)?)
+            };
+            live_entries.push((&post.id, reports));
+        }
+        let queues = [
+            (WatcherQueue::Live, live_entries),
+            (
+                WatcherQueue::Fresh,
+                fresh_debounce.iter().map(|id| (id, None)).collect(),
+            ),
+            (
+                WatcherQueue::Expired,
+                expired_debounce.iter().map(|id| (id, None)).collect(),
+            ),
+        ];
+        let mut entries = Vec::new();
+        for (queue, ids) in queues {
+            for (position, (id, reports)) in ids.into_iter().enumerate() {
+                entries.push(WatcherEntry {
+                    subreddit: subreddit.to_owned(),
+                    queue,
+                    position: i32::try_from(position).expect("Get off the computer"),
+                    post_id: id.to_string(),
+                    reports,
+                });
+            }
+        }
+
+        self.conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::delete(watcher_state_dsl::watcher_state)
+                .filter(watcher_state_dsl::subreddit.eq(subreddit))
+                .execute(&self.conn)?;
+            diesel::insert_into(watcher_state_table)
+                .values(&entries)
+                .execute(&self.conn)?;
+            Ok(())
+        })
+    }
+
+    /// Replaces the stored IDs of the comments that a subreddit's comment stream already handled.
+    /// They're kept as a watcher's fresh queue, keyed with a `:comments` suffix on the subreddit
+    pub fn save_seen_comments(
+        &self,
+        key: &str,
+        seen: &VecDeque<SmallString>,
+    ) -> anyhow::Result<()> {
+        let state = WatcherState {
+            fresh_debounce: seen.to_owned(),
+            ..WatcherState::default()
+        };
+        self.save_watcher_state(key, &state)
+    }
+
+    pub fn get_seen_comments(&self, key: &str) -> anyhow::Result<VecDeque<SmallString>> {
+        Ok(self.get_watcher_state(key)?.fresh_debounce)
+    }
+
+    pub fn get_watcher_state(&self, subreddit: &str) -> anyhow::Result<WatcherState> {
+        let entries = watcher_state_dsl::watcher_state
+            .filter(watcher_state_dsl::subreddit.eq(subreddit))
+            .order((watcher_state_dsl::queue, watcher_state_dsl::position))
+            .load::<WatcherEntry>(&self.conn)?;
+
+        let mut state = WatcherState::default();
+        let mut live_reports = BTreeMap::new();
+        for WatcherEntry {
+            queue,
+            post_id,
+            reports,
+            ..
+        } in entries
+        {
+            match queue {
+                WatcherQueue::Live => {
+                    let reports = match reports {
+                        Some(reports) => serde_json::from_str(&reports)?,
+                        None => Vec::new(),
+                    };
+                    live_reports.insert(post_id, reports);
+                }
+                WatcherQueue::Fresh => state.fresh_debounce.push_back(post_id.into()),
+                WatcherQueue::Expired => state.expired_debounce.push_back(post_id.into()),
+            }
+        }
+        state.live = posts_dsl::posts
+            .filter(posts_dsl::id.eq_any(live_reports.keys()))
+            .load::<DbPost>(&self.conn)?
+            .into_iter()
+            .map(|db_post| {
+                let reports = live_reports.remove(&db_post.id).unwrap_or_default();
+                Post {
+                    reports,
+                    ..Post::from(db_post)
+                }
+            })
+            .collect();
+
+        Ok(state)
+    }
+
     pub fn get_posts(&self, category: Category, limit: u32) -> anyhow::Result<Vec<Post>> {
         let posts = posts_dsl::posts
             .filter(posts_dsl::category.eq(category))
@@ -55,16 +318,193 @@ impl Database {
         Ok(posts)
     }
 
+    /// Oldest first, so that labeling picks up where it left off
+    pub fn get_unlabeled_posts(&self, limit: u32) -> anyhow::Result<Vec<Post>> {
+        let posts = posts_dsl::posts
+            .filter(posts_dsl::category.is_null())
+            .order(posts_dsl::created_utc.asc())
+            .limit(i64::from(limit))
+            .load::<DbPost>(&self.conn)?
+            .into_iter()
+            .map(Post::from)
+            .collect();
+        Ok(posts)
+    }
+
+    pub fn set_category(&self, id: &str, category: Category) -> anyhow::Result<()> {
+        diesel::update(posts_dsl::posts.find(id))
+            .set(posts_dsl::category.eq(category))
+            .execute(&self.conn)?;
+        Ok(())
+    }
+
+    /// Only posts from before `created_before` count. Posts are stored as soon as they're seen, so
+    /// this keeps a post from vouching for itself, and keeps evaluations from peeking ahead
     pub fn get_num_posts_with_author_and_min_karma(
         &self,
         author: &str,
         min_karma: u16,
+        created_before: OffsetDateTime,
     ) -> anyhow::Result<u32> {
         let num_posts: i64 = posts_dsl::posts
             .filter(posts_dsl::author.eq(author))
+            .filter(posts_dsl::created_utc.lt(created_before.unix_timestamp()))
             .filter(posts_dsl::score.ge(i32::from(min_karma)))
             .select(count(posts_dsl::id))
             .first(&self.conn)?;
         Ok(u32::try_from(num_posts).expect("Get off the computer"))
     }
 }
+
+fn spam_reason_name(verdict: &Verdict) -> Option<&'static str> {
+    match verdict.status() {
+        Some(Status::Spam(reason)) => Some(reason.name()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{Evidence, SpamReason};
+
+    fn post(id: &str, created: i64) -> Post {
+        Post {
+            id: id.into(),
+            created: OffsetDateTime::from_unix_timestamp(created).unwrap(),
+            ..Post::for_test("Title")
+        }
+    }
+
+    #[test]
+    fn created_round_trip() {
+        let db = Database::open(":memory:").unwrap();
+
+        // Neighboring seconds that an `f32` can't tell apart
+        let timestamps = [1_700_000_001, 1_700_000_002, 1_700_000_003];
+        let posts: Vec<_> = timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, &created)| post(&format!("post{i}"), created))
+            .collect();
+        db.upsert_posts(&posts).unwrap();
+
+        let stored: Vec<_> = db
+            .get_unlabeled_posts(10)
+            .unwrap()
+            .into_iter()
+            .map(|post| post.created.unix_timestamp())
+            .collect();
+        assert_eq!(stored, timestamps);
+    }
+
+    #[test]
+    fn watcher_state_round_trip() {
+        let db = Database::open(":memory:").unwrap();
+        let report = crate::types::Report {
+            reason: "About the game".to_owned(),
+            count: 2,
+        };
+        let posts = [
+            Post {
+                reports: vec![report],
+                ..post("live1", 1)
+            },
+            post("live2", 2),
+        ];
+        db.upsert_posts(&posts).unwrap();
+
+        let state = WatcherState {
+            live: posts.into_iter().collect(),
+            fresh_debounce: ["live2", "live1"].into_iter().map(Into::into).collect(),
+            expired_debounce: ["gone"].into_iter().map(Into::into).collect(),
+        };
+        db.save_watcher_state("rust", &state).unwrap();
+        // Saving replaces what was there before
+        db.save_watcher_state("rust", &state).unwrap();
+        // and is kept separate per subreddit
+        db.save_watcher_state("learnrust", &WatcherState::default())
+            .unwrap();
+        assert!(db.get_watcher_state("learnrust").unwrap().live.is_empty());
+
+        let restored = db.get_watcher_state("rust").unwrap();
+        let live_ids: Vec<_> = restored.live.iter().map(|post| post.id.as_str()).collect();
+        assert_eq!(live_ids, ["live1", "live2"]);
+        let restored_reports: Vec<_> = restored.live.iter().map(|post| &post.reports).collect();
+        let reports: Vec<_> = state.live.iter().map(|post| &post.reports).collect();
+        assert_eq!(restored_reports, reports);
+        assert_eq!(restored.fresh_debounce, state.fresh_debounce);
+        assert_eq!(restored.expired_debounce, state.expired_debounce);
+    }
+
+    #[test]
+    fn seen_comments_round_trip() {
+        let db = Database::open(":memory:").unwrap();
+        let seen: VecDeque<SmallString> = ["c", "b", "a"].into_iter().map(Into::into).collect();
+
+        db.save_seen_comments("rust:comments", &seen).unwrap();
+        assert_eq!(db.get_seen_comments("rust:comments").unwrap(), seen);
+        // Doesn't clobber the subreddit's own watcher
+        assert!(db.get_seen_comments("rust").unwrap().is_empty());
+    }
+
+    #[test]
+    fn repeated_decisions() {
+        let db = Database::open(":memory:").unwrap();
+        let post = post("abc123", 1);
+        let reported = |count| {
+            let reason = SpamReason::UserReports {
+                reason: "About the game".to_owned(),
+                count,
+            };
+            let evidence = Evidence::spam(reason, 0.9);
+            let verdict = Verdict::new(vec![("TrustedReports", evidence)], &Default::default());
+            let Some(Status::Spam(reason)) = verdict.status() else {
+                unreachable!()
+            };
+            let report = Action::Report(reason.to_string());
+            (verdict, report)
+        };
+        let ((first, report), (second, second_report)) = (reported(2), reported(3));
+        assert_ne!(report, second_report);
+
+        assert!(!db.has_decision(&post, &first, &report, false).unwrap());
+        db.insert_decision(&post, &first, Some(&report), false)
+            .unwrap();
+        // More reports change the rendered reason, but it's still the same report
+        assert!(db
+            .has_decision(&post, &second, &second_report, false)
+            .unwrap());
+        // Dry runs are kept separate
+        assert!(!db.has_decision(&post, &first, &report, true).unwrap());
+        assert!(!db
+            .has_decision(&post, &first, &Action::Remove, false)
+            .unwrap());
+        // and so are other reasons
+        let unknown = Verdict::new(Vec::new(), &Default::default());
+        assert!(!db.has_decision(&post, &unknown, &report, false).unwrap());
+    }
+
+    #[test]
+    fn author_reputation() {
+        let db = Database::open(":memory:").unwrap();
+        let posts: Vec<_> = [("low", 1, 1.0), ("old", 2, 10.0), ("new", 3, 10.0)]
+            .into_iter()
+            .map(|(id, created, score)| Post {
+                score,
+                ..post(id, created)
+            })
+            .collect();
+        db.upsert_posts(&posts).unwrap();
+
+        let num_posts = |created_before| {
+            let created_before = OffsetDateTime::from_unix_timestamp(created_before).unwrap();
+            db.get_num_posts_with_author_and_min_karma("someone", 3, created_before)
+                .unwrap()
+        };
+        // Posts don't vouch for themselves or for anything that came before them
+        assert_eq!(num_posts(3), 1);
+        assert_eq!(num_posts(4), 2);
+        assert_eq!(num_posts(1), 0);
+    }
+}
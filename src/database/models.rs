@@ -1,6 +1,7 @@
-use super::schema::posts;
-use crate::types::Category;
+use super::schema::{decisions, posts, verdicts, watcher_state};
+use crate::types::{Category, StatusKind};
 
+use diesel_derive_enum::DbEnum;
 // TODO: no need to micro-optimize with this kind of stuff
 use smartstring::alias::String as SmallString;
 use time::OffsetDateTime;
@@ -12,10 +13,14 @@ pub struct Post {
     pub author: String,
     pub score: i32,
     pub title: String,
-    pub created: f32,
     pub body: Option<String>,
     pub link: Option<String>,
     pub category: Option<Category>,
+    pub first_seen: Option<i64>,
+    pub last_seen: Option<i64>,
+    pub removed: bool,
+    pub created_utc: i64,
+    pub subreddit: String,
 }
 
 impl From<crate::types::Post> for Post {
@@ -29,6 +34,11 @@ impl From<crate::types::Post> for Post {
             body,
             link,
             category,
+            removed,
+            subreddit,
+            // Reports change too often to be worth storing here. The reports queue's watcher state
+            // keeps track of them instead
+            reports: _,
         }: crate::types::Post,
     ) -> Self {
         Self {
@@ -36,10 +46,14 @@ impl From<crate::types::Post> for Post {
             author: String::from(author),
             score: score as i32,
             title,
-            created: created.unix_timestamp() as f32,
             body,
             link,
             category,
+            first_seen: None,
+            last_seen: None,
+            removed,
+            created_utc: created.unix_timestamp(),
+            subreddit: String::from(subreddit),
         }
     }
 }
@@ -51,20 +65,88 @@ impl From<Post> for crate::types::Post {
             author,
             score,
             title,
-            created,
             body,
             link,
             category,
+            removed,
+            created_utc,
+            subreddit,
+            ..
         } = post;
         Self {
             id: SmallString::from(id),
             author: SmallString::from(author),
             score: score as f64,
             title,
-            created: OffsetDateTime::from_unix_timestamp(created as i64).unwrap(),
+            created: OffsetDateTime::from_unix_timestamp(created_utc).unwrap(),
             body,
             link,
             category,
+            removed,
+            subreddit: SmallString::from(subreddit),
+            reports: Vec::new(),
         }
     }
 }
+
+#[derive(Insertable)]
+#[table_name = "decisions"]
+pub struct NewDecision {
+    pub post_id: String,
+    pub status: StatusKind,
+    pub reason: Option<String>,
+    pub action: Option<String>,
+    pub action_kind: Option<String>,
+    pub spam_reason: Option<String>,
+    pub dry_run: bool,
+    pub decided_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "verdicts"]
+pub struct NewVerdict {
+    pub post_id: String,
+    pub filter: String,
+    pub status: StatusKind,
+    pub confidence: Option<f32>,
+    pub reason: Option<String>,
+    pub program_version: String,
+    pub config_hash: String,
+    pub evaluated_at: i64,
+}
+
+#[derive(DbEnum, Clone, Copy, Debug, PartialEq)]
+pub enum WatcherQueue {
+    Live,
+    Fresh,
+    Expired,
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name = "watcher_state"]
+pub struct WatcherEntry {
+    pub subreddit: String,
+    pub queue: WatcherQueue,
+    pub position: i32,
+    pub post_id: String,
+    pub reports: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn created_precision() {
+        let created = OffsetDateTime::from_unix_timestamp(1_700_000_001).unwrap();
+        let post = crate::types::Post {
+            created,
+            ..crate::types::Post::for_test("Title")
+        };
+
+        let db_post = Post::from(post);
+        assert_eq!(db_post.created_utc, 1_700_000_001);
+        let post = crate::types::Post::from(db_post);
+        assert_eq!(post.created, created);
+    }
+}
@@ -1,14 +1,58 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    decisions (id) {
+        id -> Integer,
+        post_id -> Text,
+        status -> crate::types::StatusKindMapping,
+        reason -> Nullable<Text>,
+        action -> Nullable<Text>,
+        action_kind -> Nullable<Text>,
+        spam_reason -> Nullable<Text>,
+        dry_run -> Bool,
+        decided_at -> BigInt,
+    }
+}
+
 diesel::table! {
     posts (id) {
         id -> Text,
         author -> Text,
         score -> Integer,
         title -> Text,
-        created -> Float,
         body -> Nullable<Text>,
         link -> Nullable<Text>,
         category -> Nullable<crate::types::CategoryMapping>,
+        first_seen -> Nullable<BigInt>,
+        last_seen -> Nullable<BigInt>,
+        removed -> Bool,
+        created_utc -> BigInt,
+        subreddit -> Text,
     }
 }
+
+diesel::table! {
+    verdicts (id) {
+        id -> Integer,
+        post_id -> Text,
+        filter -> Text,
+        status -> crate::types::StatusKindMapping,
+        confidence -> Nullable<Float>,
+        reason -> Nullable<Text>,
+        program_version -> Text,
+        config_hash -> Text,
+        evaluated_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    watcher_state (subreddit, queue, position) {
+        subreddit -> Text,
+        queue -> crate::database::models::WatcherQueueMapping,
+        position -> Integer,
+        post_id -> Text,
+        reports -> Nullable<Text>,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(decisions, posts, verdicts, watcher_state,);
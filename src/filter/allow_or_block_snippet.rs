@@ -0,0 +1,45 @@
+//! Matches configured phrases and regexes against the title and text of a post
+
+use super::{Context, Evidence, HamReason, SpamReason};
+use crate::{config::Config, types::Token};
+
+pub fn filter(
+    Context {
+        post,
+        config: Config {
+            snippet_filters,
+            filters,
+            ..
+        },
+        ..
+    }: Context,
+) -> Option<Evidence> {
+    let confidence = filters.allow_or_block_snippet.confidence;
+    let body_texts = post.tokens().into_iter().filter_map(|token| match token {
+        Token::Text(text) | Token::Code { text, .. } => Some(text),
+        Token::Url { .. } | Token::Image { .. } => None,
+    });
+    let texts: Vec<_> = Some(post.title.clone())
+        .into_iter()
+        .chain(body_texts)
+        .collect();
+
+    // Allowed snippets win. Things like `cargo build` only come up when talking about the language,
+    // while phrases like "looking for a group" also get used when looking for contributors
+    if let Some(snippet) = texts
+        .iter()
+        .find_map(|text| snippet_filters.allow.find(text))
+    {
+        Some(Evidence::ham(
+            HamReason::AllowedSnippet(snippet.to_owned()),
+            confidence,
+        ))
+    } else {
+        texts
+            .iter()
+            .find_map(|text| snippet_filters.block.find(text))
+            .map(|snippet| {
+                Evidence::spam(SpamReason::BlockedSnippet(snippet.to_owned()), confidence)
+            })
+    }
+}
@@ -1,52 +1,62 @@
 //! Detects good link-posts off of blessed domains
 
-use super::{Context, HamReason, SpamReason, Status};
-use crate::{config::Config, types::Token};
+use super::{Context, Evidence, HamReason, SpamReason};
+use crate::{
+    config::Config,
+    types::{Origin, Token},
+};
 
 use url::Url;
 
 pub fn filter(
     Context {
         post,
-        config: Config { url_filters },
+        config: Config {
+            url_filters,
+            filters,
+            ..
+        },
         ..
     }: Context,
-) -> Option<Status> {
-    // Filter based off the link from the post
-    post.link
-        .as_deref()
-        .and_then(|link| {
-            let url = Url::parse(link).ok()?;
+) -> Option<Evidence> {
+    let params = &filters.allow_or_block_url;
+    // The post's link comes first, so it gets the final say. Otherwise check the in-text links
+    // from the title and body
+    let mut in_text_status = None;
+    for (origin, token) in post.tokens_with_origin() {
+        let (Token::Url { url: link, .. } | Token::Image { url: link, .. }) = token else {
+            continue;
+        };
+        let Ok(url) = Url::parse(&link) else {
+            continue;
+        };
 
+        if origin == Origin::Link {
             if url_filters.allow.contains(&url) {
-                Some(Status::Ham(HamReason::AllowedUrl(link.to_owned())))
+                return Some(Evidence::ham(
+                    HamReason::AllowedUrl { url: link, origin },
+                    params.link_confidence,
+                ));
             } else if url_filters.block.contains(&url) {
-                Some(Status::Spam(SpamReason::BlockedUrl(link.to_owned())))
-            } else {
-                None
+                return Some(Evidence::spam(
+                    SpamReason::BlockedUrl { url: link, origin },
+                    params.link_confidence,
+                ));
             }
-        })
-        // Or check for in-text links
-        .or_else(|| {
-            let mut in_text_status = None;
-            for link in post.tokens().into_iter().filter_map(|token| match token {
-                Token::Url { url, .. } => Some(url),
-                _ => None,
-            }) {
-                let Ok(url) = Url::parse(&link) else {
-                    continue;
-                };
+        // Preference given to allowed links for in-text. Someone may post a youtube video and a
+        // github link for instance
+        } else if url_filters.allow.contains(&url) {
+            return Some(Evidence::ham(
+                HamReason::AllowedUrl { url: link, origin },
+                params.in_text_confidence,
+            ));
+        } else if url_filters.block.contains(&url) {
+            in_text_status = Some(Evidence::spam(
+                SpamReason::BlockedUrl { url: link, origin },
+                params.in_text_confidence,
+            ));
+        }
+    }
 
-                // Preference given to allowed links for in-text. Someone may post a youtube video
-                // and a github link for instance
-                if url_filters.allow.contains(&url) {
-                    in_text_status = Some(Status::Ham(HamReason::AllowedUrl(link)));
-                    break;
-                } else if url_filters.block.contains(&url) {
-                    in_text_status = Some(Status::Spam(SpamReason::BlockedUrl(link)));
-                }
-            }
-
-            in_text_status
-        })
+    in_text_status
 }
@@ -1,8 +1,9 @@
 use crate::{filter::HamReason, types::Token};
 
-use super::{Context, Status};
+use super::{Context, Evidence};
 
 use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use serde::Serialize;
 use syn::Ident;
 
 // These just have to be precise enough to reasonably not match Rust-Game related content
@@ -16,15 +17,22 @@ use syn::Ident;
 // - <[\w]+(, [\w]+)*,?>
 // // Scope / block
 // - Some { followed by a later }
-pub fn filter(ctx: Context) -> Option<Status> {
-    for token in ctx.post.tokens() {
+pub fn filter(ctx: Context) -> Option<Evidence> {
+    let params = &ctx.config.filters.contains_rust_code;
+    for (origin, token) in ctx.post.tokens_with_origin() {
         if let Token::Code { lang, text } = token {
             if let Some(lang) = lang {
-                return Some(Status::Ham(HamReason::FencedCodeBlock(lang)));
+                return Some(Evidence::ham(
+                    HamReason::FencedCodeBlock { lang, origin },
+                    params.fenced_confidence,
+                ));
             }
 
             if let Some(heuristic) = is_rust(&text) {
-                return Some(Status::Ham(HamReason::DetectedRustCode(heuristic)));
+                return Some(Evidence::ham(
+                    HamReason::DetectedRustCode { heuristic, origin },
+                    params.detected_confidence,
+                ));
             }
         }
     }
@@ -132,7 +140,7 @@ enum State {
     Final(Heuristic),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Heuristic {
     DoubleColon(String),
     CurlyBracePair,
@@ -161,7 +169,7 @@ impl Heuristic {
 }
 
 /// Keywords that are unlikely to appear in Rust-Game posts
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum Keyword {
     BTreeMap,
     Derive,
@@ -229,10 +237,9 @@ fn is_rust(s: &str) -> Option<Heuristic> {
 }
 
 fn is_rust_helper(tokens: TokenStream) -> Option<Heuristic> {
-    let mut it = tokens.into_iter();
     let mut state_machine = StateMachine::new();
 
-    while let Some(token) = it.next() {
+    for token in tokens {
         state_machine.munch(&token);
 
         if let ret @ Some(_) = state_machine.finished() {
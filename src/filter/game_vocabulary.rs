@@ -0,0 +1,149 @@
+//! Scores a post's prose against a weighted lexicon of Rust-the-game terms
+//!
+//! Some terms like "server" show up in plenty of programming posts too, so terms can be guarded by
+//! words that cancel them out when they're nearby
+
+use std::collections::BTreeSet;
+
+use super::{Context, Evidence, SpamReason};
+use crate::{
+    config::{GameTerm, GameVocabularyParams},
+    types::{Post, Token},
+};
+
+pub fn filter(Context { post, config, .. }: Context) -> Option<Evidence> {
+    check(post, &config.filters.game_vocabulary)
+}
+
+fn check(post: &Post, params: &GameVocabularyParams) -> Option<Evidence> {
+    // Code is left out since that's the realm of programming posts anyways
+    let body_texts = post.tokens().into_iter().filter_map(|token| match token {
+        Token::Text(text) => Some(text),
+        _ => None,
+    });
+    let texts = Some(post.title.clone()).into_iter().chain(body_texts);
+
+    let mut matched = BTreeSet::new();
+    for text in texts {
+        let words = words(&text);
+        for (term, game_term) in &params.terms {
+            if !matched.contains(term) && contains_term(&words, term, game_term, params) {
+                matched.insert(term);
+            }
+        }
+    }
+
+    let score: f32 = matched.iter().map(|&term| params.terms[term].weight).sum();
+    if score >= params.min_score {
+        let terms = matched.into_iter().cloned().collect();
+        Some(Evidence::spam(
+            SpamReason::GameVocabulary { terms, score },
+            score.min(1.0),
+        ))
+    } else {
+        None
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn contains_term(
+    words: &[String],
+    term: &str,
+    game_term: &GameTerm,
+    params: &GameVocabularyParams,
+) -> bool {
+    let term_words = words_of(term);
+    if term_words.is_empty() || words.len() < term_words.len() {
+        return false;
+    }
+
+    (0..=words.len() - term_words.len()).any(|start| {
+        let window = &words[start..start + term_words.len()];
+        let is_match = window
+            .iter()
+            .zip(&term_words)
+            .all(|(word, term_word)| is_same_word(word, term_word));
+        if !is_match {
+            return false;
+        }
+
+        let guard_start = start.saturating_sub(params.guard_window);
+        let guard_end = (start + term_words.len() + params.guard_window).min(words.len());
+        let is_guarded = words[guard_start..guard_end].iter().any(|word| {
+            game_term
+                .unless
+                .iter()
+                .any(|guard| is_same_word(word, guard))
+        });
+        !is_guarded
+    })
+}
+
+fn words_of(term: &str) -> Vec<String> {
+    term.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// Matches the word exactly or with a plural `s`
+fn is_same_word(word: &str, term: &str) -> bool {
+    word == term || word.strip_suffix('s') == Some(term)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(title: &str, body: &str) -> Post {
+        Post {
+            body: Some(body.to_owned()),
+            ..Post::for_test(title)
+        }
+    }
+
+    #[test]
+    fn game_vocabulary() {
+        let params: GameVocabularyParams = toml::from_str(
+            r#"
+            [terms]
+            base = { weight = 0.2, unless = ["case"] }
+            "looking for group" = 0.5
+            server = { weight = 0.2, unless = ["axum", "web"] }
+            wipe = 0.4
+            zerg = 0.4
+            "#,
+        )
+        .unwrap();
+
+        let spammy = post(
+            "Wipe day raid",
+            "Our base got wrecked by a zerg. Looking for group on a low pop server",
+        );
+        let evidence = check(&spammy, &params).unwrap();
+        let crate::filter::Status::Spam(SpamReason::GameVocabulary { terms, score }) =
+            evidence.status
+        else {
+            panic!("Expected game vocabulary: {evidence:?}");
+        };
+        assert!(terms.contains(&"wipe".to_owned()), "{terms:?}");
+        assert!(terms.contains(&"looking for group".to_owned()), "{terms:?}");
+        assert!(score >= params.min_score);
+
+        let hammy = post(
+            "Web server keeps crashing",
+            "My axum server falls over under load. The base case seems fine though",
+        );
+        assert!(check(&hammy, &params).is_none());
+
+        // Code blocks don't count
+        let code = post(
+            "Why won't this compile?",
+            "```\nlet raid = wipe(base);\n```",
+        );
+        assert!(check(&code, &params).is_none());
+    }
+}
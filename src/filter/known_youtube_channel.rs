@@ -1,5 +1,357 @@
-use super::{Context, Status};
+//! Checks youtube links against the allowed and blocked channels
+//!
+//! We get a steady stream of Rust-the-game videos, but the link alone doesn't tell us much, so
+//! each video gets resolved to the channel that uploaded it
 
-pub fn filter(_: Context) -> Option<Status> {
-    None
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{Context, Evidence, HamReason, SpamReason};
+use crate::{
+    config::{YoutubeChannelParams, YoutubeFilters},
+    types::{Post, Token},
+};
+
+use anyhow::Context as _;
+use url::Url;
+
+pub fn filter(
+    Context {
+        post,
+        config,
+        youtube,
+        ..
+    }: Context,
+) -> Option<Evidence> {
+    check(
+        links(post).into_iter(),
+        &config.youtube,
+        &config.filters.youtube_channel,
+        youtube,
+    )
+}
+
+/// The post's link followed by any links in its title and body
+fn links(post: &Post) -> Vec<String> {
+    post.tokens_with_origin()
+        .into_iter()
+        .filter_map(|(_, token)| match token {
+            Token::Url { url, .. } => Some(url),
+            _ => None,
+        })
+        .collect()
+}
+
+fn check(
+    links: impl Iterator<Item = String>,
+    filters: &YoutubeFilters,
+    params: &YoutubeChannelParams,
+    resolver: &dyn ChannelResolver,
+) -> Option<Evidence> {
+    let mut evidence = None;
+    for link in links {
+        let Some(video_id) = Url::parse(&link).ok().as_ref().and_then(video_id) else {
+            continue;
+        };
+        let channel_id = match resolver.channel_id(&video_id) {
+            Ok(channel_id) => channel_id,
+            Err(error) => {
+                tracing::warn!(%error, video_id, "Failed resolving youtube channel");
+                continue;
+            }
+        };
+
+        // An allowed channel settles it. Those are hand-picked Rust channels, so a post sharing
+        // one of their talks is on topic even if it also links other videos
+        if filters.allow.contains(&channel_id) {
+            return Some(Evidence::ham(
+                HamReason::KnownYoutubeChannel {
+                    channel_id,
+                    video_id,
+                },
+                params.known_confidence,
+            ));
+        } else if filters.block.contains(&channel_id) {
+            evidence = Some(Evidence::spam(
+                SpamReason::BlockedYoutubeChannel {
+                    channel_id,
+                    video_id,
+                },
+                params.known_confidence,
+            ));
+        } else if filters.block_unknown && evidence.is_none() {
+            evidence = Some(Evidence::spam(
+                SpamReason::UnknownYoutubeChannel {
+                    channel_id,
+                    video_id,
+                },
+                params.unknown_confidence,
+            ));
+        }
+    }
+
+    evidence
+}
+
+/// Pulls the video ID out of the many different flavors of youtube links
+fn video_id(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let host = host.strip_prefix("m.").unwrap_or(host);
+    let mut segments = url.path_segments()?;
+
+    let id = match host {
+        "youtu.be" => segments.next()?.to_owned(),
+        "youtube.com" => match segments.next()? {
+            "watch" => url
+                .query_pairs()
+                .find_map(|(key, value)| (key == "v").then(|| value.into_owned()))?,
+            "shorts" | "embed" | "live" | "v" => segments.next()?.to_owned(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let is_valid = id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    is_valid.then_some(id)
+}
+
+/// Resolves a video ID to the ID of the channel that uploaded it
+pub trait ChannelResolver {
+    fn channel_id(&self, video_id: &str) -> anyhow::Result<String>;
+}
+
+/// A fixed mapping of video IDs to channel IDs
+impl ChannelResolver for BTreeMap<String, String> {
+    fn channel_id(&self, video_id: &str) -> anyhow::Result<String> {
+        self.get(video_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown video"))
+    }
+}
+
+/// Snags the channel ID from the video's page, so that we don't need an API key
+pub struct PageScraper {
+    client: reqwest::blocking::Client,
+    cache: RefCell<HashMap<String, String>>,
+}
+
+impl PageScraper {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+            cache: RefCell::default(),
+        }
+    }
+}
+
+impl ChannelResolver for PageScraper {
+    fn channel_id(&self, video_id: &str) -> anyhow::Result<String> {
+        if let Some(channel_id) = self.cache.borrow().get(video_id) {
+            return Ok(channel_id.to_owned());
+        }
+
+        // TODO: stream the response to keep down memory usage
+        let body = self
+            .client
+            .get(format!("https://www.youtube.com/watch?v={video_id}"))
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let (_, chunk) = body
+            .split_once(r#"<meta itemprop="channelId" content=""#)
+            .or_else(|| body.split_once(r#""channelId":""#))
+            .ok_or_else(|| anyhow::anyhow!("Missing channel ID"))?;
+        let (channel_id, _) = chunk
+            .split_once('"')
+            .ok_or_else(|| anyhow::anyhow!("Malformed channel ID"))?;
+
+        self.cache
+            .borrow_mut()
+            .insert(video_id.to_owned(), channel_id.to_owned());
+        Ok(channel_id.to_owned())
+    }
+}
+
+/// Remembers resolved channels in a file, so that repeated runs over the same posts agree with each
+/// other and only go through `inner` for videos they haven't seen before
+pub struct CachedResolver<R> {
+    inner: R,
+    path: PathBuf,
+    cache: RefCell<BTreeMap<String, String>>,
+}
+
+impl<R: ChannelResolver> CachedResolver<R> {
+    /// Starts out empty when there's nothing at `path` yet
+    pub fn open(inner: R, path: &Path) -> anyhow::Result<Self> {
+        let cache = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid youtube channel cache {}", path.display()))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self {
+            inner,
+            path: path.to_owned(),
+            cache: RefCell::new(cache),
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let text = serde_json::to_string_pretty(&*self.cache.borrow())?;
+        fs::write(&self.path, text)?;
+        Ok(())
+    }
+}
+
+impl<R: ChannelResolver> ChannelResolver for CachedResolver<R> {
+    fn channel_id(&self, video_id: &str) -> anyhow::Result<String> {
+        if let Some(channel_id) = self.cache.borrow().get(video_id) {
+            return Ok(channel_id.to_owned());
+        }
+
+        // Failures aren't remembered, so that they get another shot on the next run
+        let channel_id = self.inner.channel_id(video_id)?;
+        self.cache
+            .borrow_mut()
+            .insert(video_id.to_owned(), channel_id.clone());
+        Ok(channel_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Status;
+
+    #[test]
+    fn video_ids() {
+        let links = [
+            ("https://youtu.be/rtage0vMbgg", Some("rtage0vMbgg")),
+            ("https://youtu.be/rtage0vMbgg?t=42", Some("rtage0vMbgg")),
+            (
+                "https://www.youtube.com/watch?v=rtage0vMbgg",
+                Some("rtage0vMbgg"),
+            ),
+            (
+                "https://m.youtube.com/watch?t=1&v=rtage0vMbgg",
+                Some("rtage0vMbgg"),
+            ),
+            (
+                "https://youtube.com/shorts/rtage0vMbgg",
+                Some("rtage0vMbgg"),
+            ),
+            (
+                "https://www.youtube.com/embed/rtage0vMbgg",
+                Some("rtage0vMbgg"),
+            ),
+            ("https://www.youtube.com/@SomeChannel", None),
+            ("https://www.youtube.com/watch?v=too-short", None),
+            ("https://notyoutube.com/watch?v=rtage0vMbgg", None),
+        ];
+
+        for (link, expected) in links {
+            let url = Url::parse(link).unwrap();
+            assert_eq!(video_id(&url).as_deref(), expected, "Link: {link}");
+        }
+    }
+
+    #[test]
+    fn links_from_everywhere() {
+        let post = Post {
+            body: Some("Or [this one](https://youtu.be/bbbbbbbbbbb)".to_owned()),
+            link: Some("https://youtu.be/ccccccccccc".to_owned()),
+            ..Post::for_test("Watch https://youtu.be/aaaaaaaaaaa")
+        };
+        assert_eq!(
+            links(&post),
+            [
+                "https://youtu.be/ccccccccccc",
+                "https://youtu.be/aaaaaaaaaaa",
+                "https://youtu.be/bbbbbbbbbbb",
+            ]
+        );
+    }
+
+    #[test]
+    fn cached_channels() {
+        let path = std::env::temp_dir().join(format!(
+            "auto_shadow0133-youtube-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let resolver: BTreeMap<_, _> = [("aaaaaaaaaaa".to_owned(), "UCallowed".to_owned())].into();
+
+        let cached = CachedResolver::open(resolver, &path).unwrap();
+        assert_eq!(cached.channel_id("aaaaaaaaaaa").unwrap(), "UCallowed");
+        assert!(cached.channel_id("bbbbbbbbbbb").is_err());
+        cached.save().unwrap();
+
+        // Later runs don't need to resolve it again
+        let cached = CachedResolver::open(BTreeMap::new(), &path).unwrap();
+        assert_eq!(cached.channel_id("aaaaaaaaaaa").unwrap(), "UCallowed");
+        assert!(cached.channel_id("bbbbbbbbbbb").is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn allow_and_block() {
+        let resolver: BTreeMap<_, _> = [
+            ("aaaaaaaaaaa", "UCallowed"),
+            ("bbbbbbbbbbb", "UCblocked"),
+            ("ccccccccccc", "UCunknown"),
+        ]
+        .into_iter()
+        .map(|(video, channel)| (video.to_owned(), channel.to_owned()))
+        .collect();
+        let mut filters = YoutubeFilters {
+            allow: ["UCallowed".to_owned()].into(),
+            block: ["UCblocked".to_owned()].into(),
+            block_unknown: false,
+        };
+        let check_links = |links: &[&str], filters: &YoutubeFilters| {
+            let links = links.iter().map(|&link| link.to_owned());
+            check(links, filters, &Default::default(), &resolver)
+        };
+
+        let allowed = "https://youtu.be/aaaaaaaaaaa";
+        let blocked = "https://youtu.be/bbbbbbbbbbb";
+        let unknown = "https://youtu.be/ccccccccccc";
+
+        assert!(matches!(
+            check_links(&[blocked, allowed], &filters),
+            Some(Evidence {
+                status: Status::Ham(HamReason::KnownYoutubeChannel { channel_id, video_id }),
+                ..
+            })
+                if channel_id == "UCallowed" && video_id == "aaaaaaaaaaa"
+        ));
+        assert!(matches!(
+            check_links(&[unknown, blocked], &filters),
+            Some(Evidence {
+                status: Status::Spam(SpamReason::BlockedYoutubeChannel { channel_id, .. }),
+                ..
+            })
+                if channel_id == "UCblocked"
+        ));
+        assert!(check_links(&[unknown], &filters).is_none());
+
+        filters.block_unknown = true;
+        assert!(matches!(
+            check_links(&[unknown], &filters),
+            Some(Evidence {
+                status: Status::Spam(SpamReason::UnknownYoutubeChannel { channel_id, .. }),
+                ..
+            })
+                if channel_id == "UCunknown"
+        ));
+    }
 }
@@ -1,67 +1,167 @@
+mod allow_or_block_snippet;
 mod allow_or_block_url;
 mod contains_rust_code;
+mod game_vocabulary;
 mod known_youtube_channel;
+mod naive_bayes;
 mod reputable_author;
+mod trusted_reports;
 
-use std::{slice, time::Instant};
+use std::{fmt, slice, time::Instant};
+
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize};
 
 use crate::{
-    config::Config,
+    config::{Config, Scoring},
     database::Database,
-    types::{Lang, Post},
+    types::{Lang, Origin, Post, StatusKind},
 };
 
+pub use known_youtube_channel::{CachedResolver, ChannelResolver, PageScraper};
+pub use naive_bayes::{init_model as init_naive_bayes_model, Model as NaiveBayesModel};
+
 pub struct FilterIter<'ctx> {
-    iter: slice::Iter<'static, Filter>,
+    iter: slice::Iter<'ctx, Filter>,
     context: Context<'ctx>,
 }
 
 impl<'ctx> FilterIter<'ctx> {
-    pub fn new(post: &'ctx Post, config: &'ctx Config, database: &'ctx Database) -> Self {
+    pub fn new(
+        post: &'ctx Post,
+        config: &'ctx Config,
+        database: &'ctx Database,
+        youtube: &'ctx dyn ChannelResolver,
+    ) -> Self {
         let context = Context {
             post,
             config,
             database,
+            youtube,
         };
 
         Self {
-            iter: FILTERS.iter(),
+            iter: config.filters.enabled.iter(),
             context,
         }
     }
 }
 
 impl Iterator for FilterIter<'_> {
-    type Item = (Filter, Option<Status>);
+    type Item = (Filter, Option<Evidence>);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|filter| {
             let start = Instant::now();
-            let maybe_status = (filter.1)(self.context);
+            let maybe_evidence = (filter.1)(self.context);
             tracing::debug!("Filter {} took {:?}", filter.name(), start.elapsed());
-            (*filter, maybe_status)
+            (*filter, maybe_evidence)
         })
     }
 }
 
 #[derive(Clone, Copy)]
-pub struct Filter(&'static str, fn(Context) -> Option<Status>);
+pub struct Filter(&'static str, fn(Context) -> Option<Evidence>);
 
 impl Filter {
     pub fn name(&self) -> &'static str {
         self.0
     }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        FILTERS.iter().find(|filter| filter.name() == name).copied()
+    }
+
+    /// The filters that run when none are configured, in the default order. Leaves out the ones
+    /// that need some setup first
+    pub fn defaults() -> Vec<Self> {
+        FILTERS
+            .iter()
+            .filter(|filter| !OPT_IN.contains(&filter.name()))
+            .copied()
+            .collect()
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        FILTERS.iter().map(Filter::name)
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Self::from_name(&name).ok_or_else(|| {
+            let names: Vec<_> = Self::names().collect();
+            DeError::custom(format!(
+                "Unknown filter `{name}`. Expected one of {names:?}"
+            ))
+        })
+    }
 }
 
 const FILTERS: &[Filter] = &[
     Filter("AllowOrBlockUrl", allow_or_block_url::filter),
+    Filter("AllowOrBlockSnippet", allow_or_block_snippet::filter),
     Filter("ReputableAuthor", reputable_author::filter),
     Filter("YoutubeChannel", known_youtube_channel::filter),
     Filter("ContainsRustCode", contains_rust_code::filter),
+    Filter("GameVocabulary", game_vocabulary::filter),
+    Filter("TrustedReports", trusted_reports::filter),
+    Filter("NaiveBayes", naive_bayes::filter),
 ];
 
-pub fn filter(post: &Post, config: &Config, database: &Database) -> Option<Status> {
-    FilterIter::new(post, config, database).find_map(|(_, maybe_status)| maybe_status)
+/// Filters that only run when they're enabled explicitly. The naive bayes classifier needs a model
+/// from the `train` command
+const OPT_IN: &[&str] = &["NaiveBayes"];
+
+/// Loads anything that the enabled filters need up front, so that problems show up at startup
+/// instead of filters quietly sitting out
+pub fn init(config: &Config) -> anyhow::Result<()> {
+    let uses_naive_bayes = Some(&config.filters.enabled)
+        .into_iter()
+        .chain(
+            config
+                .subreddits
+                .iter()
+                .filter_map(|subreddit| subreddit.enabled_filters.as_ref()),
+        )
+        .any(|enabled| enabled.iter().any(|filter| filter.name() == "NaiveBayes"));
+    if uses_naive_bayes {
+        let model = NaiveBayesModel::load(&config.filters.naive_bayes.model_path)?;
+        naive_bayes::init_model(model);
+    }
+
+    Ok(())
+}
+
+/// Runs every filter and weighs all of the evidence against each other
+pub fn filter(
+    post: &Post,
+    config: &Config,
+    database: &Database,
+    youtube: &dyn ChannelResolver,
+) -> Verdict {
+    let mut evidence = Vec::new();
+    let mut abstained = Vec::new();
+    for (filter, maybe_evidence) in FilterIter::new(post, config, database, youtube) {
+        match maybe_evidence {
+            Some(e) => evidence.push((filter.name(), e)),
+            None => abstained.push(filter.name()),
+        }
+    }
+
+    Verdict {
+        abstained,
+        ..Verdict::new(evidence, &config.scoring)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -69,6 +169,97 @@ pub struct Context<'a> {
     post: &'a Post,
     config: &'a Config,
     database: &'a Database,
+    youtube: &'a dyn ChannelResolver,
+}
+
+/// The final call on a post along with everything that contributed to it
+#[derive(Debug)]
+pub struct Verdict {
+    pub kind: StatusKind,
+    /// Sum of the signed scores of all the evidence. Positive leans towards spam
+    pub score: f32,
+    /// Evidence from each filter that had an opinion, keyed by the filter's name
+    pub evidence: Vec<(&'static str, Evidence)>,
+    /// Names of the filters that ran, but didn't have an opinion
+    pub abstained: Vec<&'static str>,
+}
+
+impl Verdict {
+    pub fn new(evidence: Vec<(&'static str, Evidence)>, thresholds: &Scoring) -> Self {
+        let score = evidence.iter().map(|(_, evidence)| evidence.score()).sum();
+        let kind = if score >= thresholds.spam_threshold {
+            StatusKind::Spam
+        } else if score <= thresholds.ham_threshold {
+            StatusKind::Ham
+        } else {
+            StatusKind::Unknown
+        };
+
+        Self {
+            kind,
+            score,
+            evidence,
+            abstained: Vec::new(),
+        }
+    }
+
+    /// The most confident piece of evidence that agrees with the verdict
+    pub fn status(&self) -> Option<&Status> {
+        self.evidence
+            .iter()
+            .map(|(_, evidence)| evidence)
+            .filter(|evidence| StatusKind::new(Some(&evidence.status)) == self.kind)
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+            .map(|evidence| &evidence.status)
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({:+.2})", self.kind, self.score)?;
+        for (name, evidence) in &self.evidence {
+            write!(
+                f,
+                "\n  {:+.2} {name}: {:?}",
+                evidence.score(),
+                evidence.status
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single filter's opinion on a post
+#[derive(Debug)]
+pub struct Evidence {
+    pub status: Status,
+    /// How sure the filter is in the range `0.0..=1.0`
+    pub confidence: f32,
+}
+
+impl Evidence {
+    pub fn spam(reason: SpamReason, confidence: f32) -> Self {
+        Self {
+            status: Status::Spam(reason),
+            confidence,
+        }
+    }
+
+    pub fn ham(reason: HamReason, confidence: f32) -> Self {
+        Self {
+            status: Status::Ham(reason),
+            confidence,
+        }
+    }
+
+    /// The confidence signed by the kind of status. Positive for spam and negative for ham
+    pub fn score(&self) -> f32 {
+        match self.status {
+            Status::Spam(_) => self.confidence,
+            Status::Ham(_) => -self.confidence,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -77,22 +268,243 @@ pub enum Status {
     Ham(HamReason),
 }
 
-#[derive(Debug)]
+impl StatusKind {
+    pub fn new(maybe_status: Option<&Status>) -> Self {
+        match maybe_status {
+            Some(Status::Spam(_)) => Self::Spam,
+            Some(Status::Ham(_)) => Self::Ham,
+            None => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub enum SpamReason {
-    BlockedUrl(String),
+    BlockedUrl {
+        url: String,
+        origin: Origin,
+    },
     BlockedSnippet(String),
-    UnknownYoutubeChannel(()),
+    BlockedYoutubeChannel {
+        channel_id: String,
+        video_id: String,
+    },
+    UnknownYoutubeChannel {
+        channel_id: String,
+        video_id: String,
+    },
+    Classifier {
+        probability: f32,
+        top_tokens: Vec<String>,
+    },
+    GameVocabulary {
+        terms: Vec<String>,
+        score: f32,
+    },
+    UserReports {
+        reason: String,
+        count: u32,
+    },
 }
 
-#[derive(Debug)]
+impl SpamReason {
+    /// The names of every variant, used to key the per-reason action policy
+    pub const NAMES: &'static [&'static str] = &[
+        "BlockedUrl",
+        "BlockedSnippet",
+        "BlockedYoutubeChannel",
+        "UnknownYoutubeChannel",
+        "Classifier",
+        "GameVocabulary",
+        "UserReports",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BlockedUrl { .. } => "BlockedUrl",
+            Self::BlockedSnippet(_) => "BlockedSnippet",
+            Self::BlockedYoutubeChannel { .. } => "BlockedYoutubeChannel",
+            Self::UnknownYoutubeChannel { .. } => "UnknownYoutubeChannel",
+            Self::Classifier { .. } => "Classifier",
+            Self::GameVocabulary { .. } => "GameVocabulary",
+            Self::UserReports { .. } => "UserReports",
+        }
+    }
+}
+
+/// Human readable reason that gets used for reports and removal comments
+impl fmt::Display for SpamReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockedUrl { url, .. } => write!(f, "Links to a blocked site: {url}"),
+            Self::BlockedSnippet(snippet) => write!(f, "Contains a blocked phrase: \"{snippet}\""),
+            Self::BlockedYoutubeChannel {
+                channel_id,
+                video_id,
+            } => write!(
+                f,
+                "Video {video_id} is from a blocked youtube channel ({channel_id})"
+            ),
+            Self::UnknownYoutubeChannel {
+                channel_id,
+                video_id,
+            } => write!(
+                f,
+                "Video {video_id} is from an unknown youtube channel ({channel_id})"
+            ),
+            Self::Classifier {
+                probability,
+                top_tokens,
+            } => write!(
+                f,
+                "Classified as a Rust-the-game post ({:.0}% sure) off of {}",
+                probability * 100.0,
+                top_tokens.join(", ")
+            ),
+            Self::GameVocabulary { terms, score } => write!(
+                f,
+                "Uses Rust-the-game terms ({score:.2}): {}",
+                terms.join(", ")
+            ),
+            Self::UserReports { reason, count } => {
+                write!(f, "Reported by users {count} times: \"{reason}\"")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub enum HamReason {
-    AllowedUrl(String),
+    AllowedUrl {
+        url: String,
+        origin: Origin,
+    },
     AllowedSnippet(String),
-    DetectedRustCode(contains_rust_code::Heuristic),
-    FencedCodeBlock(Lang),
-    KnownYoutubeChannel(()),
+    DetectedRustCode {
+        heuristic: contains_rust_code::Heuristic,
+        origin: Origin,
+    },
+    FencedCodeBlock {
+        lang: Lang,
+        origin: Origin,
+    },
+    KnownYoutubeChannel {
+        channel_id: String,
+        video_id: String,
+    },
     ReputableAuthor {
         author: String,
         num_reputable_posts: u32,
     },
+    Classifier {
+        probability: f32,
+        top_tokens: Vec<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdict() {
+        let thresholds = Scoring::default();
+        let reputable_author = || {
+            let reason = HamReason::ReputableAuthor {
+                author: "someone".to_owned(),
+                num_reputable_posts: 10,
+            };
+            ("ReputableAuthor", Evidence::ham(reason, 0.5))
+        };
+        let blocked_url = || {
+            let reason = SpamReason::BlockedUrl {
+                url: "https://discord.gg/invite".to_owned(),
+                origin: Origin::Link,
+            };
+            ("AllowOrBlockUrl", Evidence::spam(reason, 0.9))
+        };
+        let blocked_snippet = || {
+            let reason = SpamReason::BlockedSnippet("wipe day".to_owned());
+            ("AllowOrBlockSnippet", Evidence::spam(reason, 0.7))
+        };
+
+        let verdict = Verdict::new(Vec::new(), &thresholds);
+        assert_eq!(verdict.kind, StatusKind::Unknown);
+        assert!(verdict.status().is_none());
+
+        // A regular posting a discord link isn't enough to go either way
+        let verdict = Verdict::new(vec![reputable_author(), blocked_url()], &thresholds);
+        assert_eq!(verdict.kind, StatusKind::Unknown);
+        assert!(verdict.status().is_none());
+        assert_eq!(verdict.evidence.len(), 2);
+
+        let evidence = vec![blocked_snippet(), reputable_author(), blocked_url()];
+        let verdict = Verdict::new(evidence, &thresholds);
+        assert_eq!(verdict.kind, StatusKind::Spam);
+        assert!((verdict.score - 1.1).abs() < 1e-4);
+        assert!(matches!(
+            verdict.status(),
+            Some(Status::Spam(SpamReason::BlockedUrl { .. }))
+        ));
+
+        let verdict = Verdict::new(vec![reputable_author()], &thresholds);
+        assert_eq!(verdict.kind, StatusKind::Ham);
+    }
+
+    #[test]
+    fn naive_bayes_is_opt_in() {
+        let with_settings = |settings: &str| {
+            let config = format!("[url]\nallow = []\nblock = []\n\n{settings}");
+            toml::from_str::<Config>(&config).unwrap()
+        };
+
+        // Configs from before the classifier don't need a model
+        let config = with_settings("");
+        assert!(!config
+            .filters
+            .enabled
+            .iter()
+            .any(|f| f.name() == "NaiveBayes"));
+        init(&config).unwrap();
+
+        let config = with_settings(
+            "[filters]\nenabled = [\"NaiveBayes\"]\nNaiveBayes = { model_path = \"missing.json\" }",
+        );
+        assert!(init(&config).is_err());
+    }
+
+    #[test]
+    fn spam_reason_names() {
+        let reasons = [
+            SpamReason::BlockedUrl {
+                url: "https://discord.gg/invite".to_owned(),
+                origin: Origin::Link,
+            },
+            SpamReason::BlockedSnippet("wipe day".to_owned()),
+            SpamReason::BlockedYoutubeChannel {
+                channel_id: "UC123".to_owned(),
+                video_id: "abc".to_owned(),
+            },
+            SpamReason::UnknownYoutubeChannel {
+                channel_id: "UC123".to_owned(),
+                video_id: "abc".to_owned(),
+            },
+            SpamReason::Classifier {
+                probability: 0.9,
+                top_tokens: Vec::new(),
+            },
+            SpamReason::GameVocabulary {
+                terms: Vec::new(),
+                score: 0.6,
+            },
+            SpamReason::UserReports {
+                reason: "About the game".to_owned(),
+                count: 2,
+            },
+        ];
+
+        // Keeps the policy keys in sync with the variants
+        let names: Vec<_> = reasons.iter().map(SpamReason::name).collect();
+        assert_eq!(names, SpamReason::NAMES);
+    }
 }
@@ -0,0 +1,289 @@
+//! A multinomial naive bayes classifier trained off of the labeled posts
+//!
+//! The model gets trained with the `train` command and is loaded at startup when the filter is
+//! enabled
+
+use std::{collections::BTreeMap, fs, path::Path, sync::OnceLock};
+
+use super::{Context, Evidence, HamReason, SpamReason};
+use crate::types::{Post, Token};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+const NUM_TOP_TOKENS: usize = 5;
+
+static GLOBAL_MODEL: OnceLock<Model> = OnceLock::new();
+
+pub fn init_model(model: Model) {
+    GLOBAL_MODEL.get_or_init(|| model);
+}
+
+pub fn expect_model() -> &'static Model {
+    GLOBAL_MODEL
+        .get()
+        .expect("The model is loaded at startup when the filter is enabled")
+}
+
+pub fn filter(Context { post, config, .. }: Context) -> Option<Evidence> {
+    let params = &config.filters.naive_bayes;
+    classify(expect_model(), post, params.min_probability)
+}
+
+fn classify(model: &Model, post: &Post, min_probability: f32) -> Option<Evidence> {
+    let tokens = tokenize(post);
+    let spam_probability = model.spam_probability(&tokens);
+    let is_spam = spam_probability >= 0.5;
+    let probability = if is_spam {
+        spam_probability
+    } else {
+        1.0 - spam_probability
+    };
+
+    if probability < min_probability {
+        return None;
+    }
+
+    // Map `0.5..=1.0` onto `0.0..=1.0`, so that a coin-flip has no weight
+    let confidence = (probability - 0.5) * 2.0;
+    let evidence = if is_spam {
+        let top_tokens = model.top_tokens(&tokens, &model.spam, &model.ham);
+        Evidence::spam(
+            SpamReason::Classifier {
+                probability,
+                top_tokens,
+            },
+            confidence,
+        )
+    } else {
+        let top_tokens = model.top_tokens(&tokens, &model.ham, &model.spam);
+        Evidence::ham(
+            HamReason::Classifier {
+                probability,
+                top_tokens,
+            },
+            confidence,
+        )
+    };
+
+    Some(evidence)
+}
+
+/// Lowercased words from the title along with any text and code from the body
+fn tokenize(post: &Post) -> Vec<String> {
+    let body_texts = post.tokens().into_iter().filter_map(|token| match token {
+        Token::Text(text) | Token::Code { text, .. } => Some(text),
+        Token::Url { .. } | Token::Image { .. } => None,
+    });
+
+    Some(post.title.clone())
+        .into_iter()
+        .chain(body_texts)
+        .flat_map(|text| {
+            text.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|word| word.chars().count() > 1)
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Model {
+    ham: ClassCounts,
+    spam: ClassCounts,
+    vocab_size: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ClassCounts {
+    num_docs: u32,
+    num_tokens: u64,
+    tokens: BTreeMap<String, u32>,
+}
+
+impl ClassCounts {
+    fn add(&mut self, tokens: Vec<String>) {
+        self.num_docs += 1;
+        for token in tokens {
+            self.num_tokens += 1;
+            *self.tokens.entry(token).or_default() += 1;
+        }
+    }
+
+    /// Laplace smoothed log-likelihood of the token showing up in this class
+    fn log_likelihood(&self, token: &str, vocab_size: u32) -> f64 {
+        let count = self.tokens.get(token).copied().unwrap_or_default();
+        let numer = f64::from(count) + 1.0;
+        let denom = self.num_tokens as f64 + f64::from(vocab_size);
+        (numer / denom).ln()
+    }
+}
+
+impl Model {
+    pub fn train(ham: &[Post], spam: &[Post]) -> Self {
+        let mut model = Self::default();
+        for post in ham {
+            model.ham.add(tokenize(post));
+        }
+        for post in spam {
+            model.spam.add(tokenize(post));
+        }
+
+        let mut vocab: Vec<_> = model.ham.tokens.keys().collect();
+        vocab.extend(model.spam.tokens.keys());
+        vocab.sort_unstable();
+        vocab.dedup();
+        model.vocab_size = u32::try_from(vocab.len()).expect("Get off the computer");
+
+        model
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed loading the naive bayes model from {}. Train one with the `train` command \
+                or disable the `NaiveBayes` filter",
+                path.display()
+            )
+        })?;
+        let model = serde_json::from_str(&text)?;
+        Ok(model)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = serde_json::to_string(self)?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn num_docs(&self) -> (u32, u32) {
+        (self.ham.num_docs, self.spam.num_docs)
+    }
+
+    fn is_known(&self, token: &str) -> bool {
+        self.ham.tokens.contains_key(token) || self.spam.tokens.contains_key(token)
+    }
+
+    fn spam_probability(&self, tokens: &[String]) -> f32 {
+        let total_docs = f64::from(self.ham.num_docs + self.spam.num_docs);
+        if self.ham.num_docs == 0 || self.spam.num_docs == 0 {
+            return 0.5;
+        }
+
+        let mut log_ham = (f64::from(self.ham.num_docs) / total_docs).ln();
+        let mut log_spam = (f64::from(self.spam.num_docs) / total_docs).ln();
+        // Tokens we've never seen don't tell us anything
+        for token in tokens.iter().filter(|token| self.is_known(token)) {
+            log_ham += self.ham.log_likelihood(token, self.vocab_size);
+            log_spam += self.spam.log_likelihood(token, self.vocab_size);
+        }
+
+        (1.0 / (1.0 + (log_ham - log_spam).exp())) as f32
+    }
+
+    /// The tokens that pushed the hardest towards `winner` over `loser`
+    fn top_tokens(
+        &self,
+        tokens: &[String],
+        winner: &ClassCounts,
+        loser: &ClassCounts,
+    ) -> Vec<String> {
+        let mut contributions: Vec<_> = tokens
+            .iter()
+            .filter(|token| self.is_known(token))
+            .map(|token| {
+                let contribution = winner.log_likelihood(token, self.vocab_size)
+                    - loser.log_likelihood(token, self.vocab_size);
+                (token, contribution)
+            })
+            .filter(|(_, contribution)| *contribution > 0.0)
+            .collect();
+        contributions.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut top_tokens: Vec<String> = Vec::new();
+        for (token, _) in contributions {
+            if !top_tokens.contains(token) {
+                top_tokens.push(token.to_owned());
+            }
+            if top_tokens.len() == NUM_TOP_TOKENS {
+                break;
+            }
+        }
+
+        top_tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Status;
+
+    fn post(title: &str, body: &str) -> Post {
+        Post {
+            body: Some(body.to_owned()),
+            ..Post::for_test(title)
+        }
+    }
+
+    #[test]
+    fn classify_posts() {
+        let ham = [
+            post(
+                "Borrow checker woes",
+                "Why does the borrow checker reject this closure?",
+            ),
+            post(
+                "Async trait question",
+                "How do I box a future returned from a trait?",
+            ),
+            post(
+                "Lifetimes in structs",
+                "The compiler says my struct needs a lifetime",
+            ),
+        ];
+        let spam = [
+            post(
+                "Wipe day raid",
+                "Our base got raided right after wipe, need a duo",
+            ),
+            post(
+                "Server recommendations",
+                "Looking for a low pop server with a chill wipe",
+            ),
+            post(
+                "Best base design",
+                "Raided again. Any base designs that hold up to rockets?",
+            ),
+        ];
+        let model = Model::train(&ham, &spam);
+        assert_eq!(model.num_docs(), (3, 3));
+
+        let spammy = post("Raid after wipe", "Our base is gone");
+        let evidence = classify(&model, &spammy, 0.9).unwrap();
+        let Status::Spam(SpamReason::Classifier {
+            probability,
+            top_tokens,
+        }) = evidence.status
+        else {
+            panic!("Expected spam: {evidence:?}");
+        };
+        assert!(probability >= 0.9);
+        assert!(top_tokens.contains(&"wipe".to_owned()), "{top_tokens:?}");
+
+        let hammy = post(
+            "Borrow checker and lifetimes",
+            "The compiler rejects my closure",
+        );
+        let evidence = classify(&model, &hammy, 0.9).unwrap();
+        assert!(matches!(
+            evidence.status,
+            Status::Ham(HamReason::Classifier { .. })
+        ));
+
+        // Nothing to go off of
+        let unknown = post("Hello", "Goodbye");
+        assert!(classify(&model, &unknown, 0.9).is_none());
+    }
+}
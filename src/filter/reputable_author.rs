@@ -1,19 +1,26 @@
-use super::{Context, HamReason, Status};
+use super::{Context, Evidence, HamReason};
 
-// TODO: move to config
-const KARMA_THRESHOLD: u16 = 3;
-const NUM_POSTS_THRESHOLD: u32 = 2;
-
-pub fn filter(Context { post, database, .. }: Context) -> Option<Status> {
+pub fn filter(
+    Context {
+        post,
+        config,
+        database,
+        ..
+    }: Context,
+) -> Option<Evidence> {
+    let params = &config.filters.reputable_author;
     let num_posts = database
-        .get_num_posts_with_author_and_min_karma(&post.author, KARMA_THRESHOLD)
+        .get_num_posts_with_author_and_min_karma(&post.author, params.karma_threshold, post.created)
         .ok()?;
 
-    if num_posts >= NUM_POSTS_THRESHOLD {
-        Some(Status::Ham(HamReason::ReputableAuthor {
-            author: post.author.to_string(),
-            num_reputable_posts: num_posts,
-        }))
+    if num_posts >= params.num_posts_threshold {
+        Some(Evidence::ham(
+            HamReason::ReputableAuthor {
+                author: post.author.to_string(),
+                num_reputable_posts: num_posts,
+            },
+            params.confidence,
+        ))
     } else {
         None
     }
@@ -0,0 +1,80 @@
+//! Trusts users reporting a post for being about the game once enough of them agree
+
+use super::{Context, Evidence, SpamReason};
+use crate::{config::TrustedReportsParams, types::Post};
+
+pub fn filter(Context { post, config, .. }: Context) -> Option<Evidence> {
+    check(post, &config.filters.trusted_reports)
+}
+
+fn check(post: &Post, params: &TrustedReportsParams) -> Option<Evidence> {
+    let matching: Vec<_> = post
+        .reports
+        .iter()
+        .filter(|report| {
+            let reason = report.reason.to_lowercase();
+            params
+                .reasons
+                .iter()
+                .any(|needle| reason.contains(&needle.to_lowercase()))
+        })
+        .collect();
+    let count = matching.iter().map(|report| report.count).sum();
+    if count < params.min_reports {
+        return None;
+    }
+
+    let most_common = matching.iter().max_by_key(|report| report.count)?;
+    Some(Evidence::spam(
+        SpamReason::UserReports {
+            reason: most_common.reason.clone(),
+            count,
+        },
+        params.confidence,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Report;
+
+    fn reported(reports: &[(&str, u32)]) -> Post {
+        Post {
+            reports: reports
+                .iter()
+                .map(|&(reason, count)| Report {
+                    reason: reason.to_owned(),
+                    count,
+                })
+                .collect(),
+            ..Post::for_test("Title")
+        }
+    }
+
+    #[test]
+    fn trusted_reports() {
+        let params = TrustedReportsParams {
+            min_reports: 3,
+            ..Default::default()
+        };
+
+        assert!(check(&reported(&[]), &params).is_none());
+        // Not enough people agree
+        let post = reported(&[("This is about the game", 2), ("Spam", 4)]);
+        assert!(check(&post, &params).is_none());
+
+        let post = reported(&[
+            ("This is about the game", 2),
+            ("Wrong sub, try r/PlayRust", 1),
+        ]);
+        let evidence = check(&post, &params).unwrap();
+        let crate::filter::Status::Spam(SpamReason::UserReports { reason, count }) =
+            evidence.status
+        else {
+            panic!("Expected user reports: {evidence:?}");
+        };
+        assert_eq!(reason, "This is about the game");
+        assert_eq!(count, 3);
+    }
+}
@@ -1,27 +1,6 @@
-// TODO: Have a list of allow-listed channels for youtube links
-//     - Youtube links should check if the title is a link, the post is a link, or the body is a
-//     link
-//     - Probably need to go through the yt api to keep youtube from getting angry
 // TODO: setup a telegram bot for interactions?
 // TODO: setup a custom tokenizer. Use a markdown parser when tokenizing
 
-// Can snag channel ID for
-// ```rust
-// TODO: stream the response to keep down memory usage
-// fn main() {
-//     let body = reqwest::blocking::get("https://youtu.be/rtage0vMbgg")
-//         .unwrap()
-//         .text()
-//         .unwrap();
-//     let (_, chunk) = body
-//         .split_once(r#"<meta itemprop="channelId" content=""#)
-//         .unwrap();
-//     let (id, _) = chunk.split_once(r#"">"#).unwrap();
-//
-//     println!("{id}");
-// }
-// ```
-
 use clap::Parser;
 
 #[macro_use]
@@ -29,6 +8,7 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
+mod action;
 mod cli;
 mod commands;
 mod config;
@@ -48,7 +28,20 @@ fn main() -> anyhow::Result<()> {
 
     match cli::Args::parse().command {
         cli::Command::Analyze => commands::analyze::run()?,
-        cli::Command::Watch => commands::watch::run()?,
+        cli::Command::Evaluate => commands::evaluate::run()?,
+        cli::Command::Label => commands::label::run()?,
+        cli::Command::Train => commands::train::run()?,
+        cli::Command::Watch {
+            dry_run,
+            replay,
+            record,
+            redact_authors,
+        } => {
+            let maybe_recorder = record
+                .map(|dir| reddit::Recorder::new(dir, redact_authors))
+                .transpose()?;
+            commands::watch::run(dry_run, replay, maybe_recorder)?
+        }
     }
 
     Ok(())
@@ -0,0 +1,213 @@
+//! Streams a subreddit's latest comments
+//!
+//! Comments only ever get added to the listing as far as we care, so unlike posts there's no live
+//! set to diff against. Just the IDs of the comments that were already emitted, which get saved
+//! alongside the watchers' state
+
+use std::{collections::VecDeque, time::Instant};
+
+use super::{Api, FetchError, RateLimit, Scheduler};
+use crate::{config::WatcherSettings, types::Comment};
+
+use roux::comment::{CommentData, Comments};
+use smartstring::alias::String as SmallString;
+use time::OffsetDateTime;
+
+/// The most comments that reddit will hand out in one go
+const NUM_LATEST_COMMENTS: u32 = 100;
+const ID_BUFFER: usize = NUM_LATEST_COMMENTS as usize * 5;
+
+/// Where the comment stream gets the latest comments from
+pub trait CommentSource {
+    fn comments(&mut self) -> Result<Vec<Comment>, FetchError>;
+
+    /// What's left of the request budget as of the last fetch
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+struct CommentFeed {
+    name: String,
+    api: Api,
+}
+
+impl CommentSource for CommentFeed {
+    fn comments(&mut self) -> Result<Vec<Comment>, FetchError> {
+        let query = [("limit", NUM_LATEST_COMMENTS.to_string())];
+        let text = self.api.get(&format!("r/{}/comments", self.name), &query)?;
+
+        let listing: Comments = serde_json::from_str(&text).map_err(|error| {
+            FetchError::Unexpected(format!("Invalid comments listing: {error}"))
+        })?;
+        let comments = listing
+            .data
+            .children
+            .into_iter()
+            .filter_map(|child| to_comment(child.data))
+            .collect();
+        Ok(comments)
+    }
+
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.api.maybe_rate_limit.as_ref()
+    }
+}
+
+/// Everything in a comment's data is optional. Comments missing what we need are skipped
+fn to_comment(
+    CommentData {
+        id,
+        link_id,
+        author,
+        score,
+        body,
+        created_utc,
+        subreddit,
+        ..
+    }: CommentData,
+) -> Option<Comment> {
+    let post_id = link_id?;
+    let post_id = post_id.strip_prefix("t3_").unwrap_or(&post_id);
+    let body = body?;
+    let created = OffsetDateTime::from_unix_timestamp(created_utc? as i64).ok()?;
+
+    Some(Comment {
+        id: SmallString::from(id?),
+        post_id: SmallString::from(post_id),
+        author: SmallString::from(author?),
+        score: score.unwrap_or_default().into(),
+        removed: body == "[removed]",
+        body,
+        created,
+        subreddit: SmallString::from(subreddit?),
+    })
+}
+
+pub struct CommentStream {
+    source: Box<dyn CommentSource>,
+    seen: VecDeque<SmallString>,
+    scheduler: Scheduler,
+    next_poll: Instant,
+}
+
+impl CommentStream {
+    pub fn new(subreddit: &str, seen: VecDeque<SmallString>, settings: &WatcherSettings) -> Self {
+        let source = CommentFeed {
+            name: subreddit.to_owned(),
+            api: Api::default(),
+        };
+        Self::with_source(Box::new(source), seen, settings)
+    }
+
+    pub fn with_source(
+        source: Box<dyn CommentSource>,
+        seen: VecDeque<SmallString>,
+        settings: &WatcherSettings,
+    ) -> Self {
+        Self {
+            source,
+            seen,
+            scheduler: Scheduler::new(settings),
+            next_poll: Instant::now(),
+        }
+    }
+
+    /// IDs of the comments that were already emitted, newest first
+    pub fn seen(&self) -> &VecDeque<SmallString> {
+        &self.seen
+    }
+
+    /// When the stream should next be updated
+    pub fn next_poll(&self) -> Instant {
+        self.next_poll
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_poll
+    }
+
+    /// Comments that haven't been emitted before, oldest first
+    pub fn update(&mut self) -> Vec<Comment> {
+        let maybe_latest = self.source.comments();
+        self.next_poll = self
+            .scheduler
+            .next_poll(&maybe_latest, self.source.rate_limit());
+
+        let Ok(mut comments) = maybe_latest else {
+            // Already logged by the scheduler
+            return Vec::new();
+        };
+        comments.retain(|comment| !self.seen.contains(&comment.id));
+        comments.sort_by_key(|comment| comment.created);
+        for comment in &comments {
+            while self.seen.len() >= ID_BUFFER {
+                self.seen.pop_back();
+            }
+
+            self.seen.push_front(comment.id.clone());
+        }
+
+        comments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    /// Hands out canned results in order
+    struct FakeComments(VecDeque<Result<Vec<(&'static str, i64)>, FetchError>>);
+
+    impl CommentSource for FakeComments {
+        fn comments(&mut self) -> Result<Vec<Comment>, FetchError> {
+            let comments = self.0.pop_front().expect("Polled too many times")?;
+            let comments = comments
+                .into_iter()
+                .map(|(id, created_utc)| {
+                    let data = json!({
+                        "id": id,
+                        "link_id": "t3_abc123",
+                        "author": "someone",
+                        "score": 1,
+                        "body": format!("Comment {id}"),
+                        "created_utc": created_utc as f64,
+                        "subreddit": "rust",
+                    });
+                    to_comment(serde_json::from_value(data).unwrap()).unwrap()
+                })
+                .collect();
+            Ok(comments)
+        }
+    }
+
+    #[test]
+    fn comment_stream() {
+        let results = vec![
+            // Newest first like reddit returns them
+            Ok(vec![("b", 2), ("a", 1)]),
+            Err(FetchError::Network("connection reset".to_owned())),
+            Ok(vec![("d", 4), ("c", 3), ("b", 2)]),
+        ];
+        // Picking up where a previous run left off
+        let seen = ["a"].into_iter().map(Into::into).collect();
+        let mut stream = CommentStream::with_source(
+            Box::new(FakeComments(results.into())),
+            seen,
+            &Default::default(),
+        );
+        let ids = |comments: Vec<Comment>| -> Vec<SmallString> {
+            comments.into_iter().map(|comment| comment.id).collect()
+        };
+
+        let comments = stream.update();
+        assert_eq!(comments[0].post_id, "abc123");
+        assert_eq!(ids(comments), ["b"]);
+        assert!(stream.update().is_empty());
+        assert!(!stream.is_due());
+        assert_eq!(ids(stream.update()), ["c", "d"]);
+        assert_eq!(stream.seen(), &["d", "c", "b", "a"]);
+    }
+}
@@ -0,0 +1,938 @@
+mod comments;
+mod record;
+mod replay;
+mod reports;
+mod schedule;
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{config::WatcherSettings, types::Post, utils};
+
+use reqwest::{
+    blocking::Response,
+    header::{AsHeaderName, HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+use roux::{
+    submission::{SubmissionData, Submissions},
+    util::RouxError,
+    Me,
+};
+use smartstring::alias::String as SmallString;
+use time::OffsetDateTime;
+use url::Url;
+
+pub use comments::CommentStream;
+pub use record::Recorder;
+pub use replay::{ReplaySource, Snapshot};
+use reports::ReportsFeed;
+use schedule::Scheduler;
+
+#[derive(Debug, Default)]
+pub struct Update {
+    pub fresh: Vec<Post>,
+    pub expired: Vec<Post>,
+}
+
+impl Update {
+    pub fn is_empty(&self) -> bool {
+        self.fresh.is_empty() && self.expired.is_empty()
+    }
+}
+
+/// What a source fetched in one go
+#[derive(Debug, Default)]
+pub struct Listing {
+    /// The latest posts, which stay live until the next fetch
+    pub latest: BTreeSet<Post>,
+    /// Older posts from past `latest` that were only fetched to catch up after falling behind.
+    /// They're new to us, but the next fetch won't include them, so they never go live
+    pub caught_up: BTreeSet<Post>,
+}
+
+impl FromIterator<Post> for Listing {
+    fn from_iter<I: IntoIterator<Item = Post>>(posts: I) -> Self {
+        Self {
+            latest: posts.into_iter().collect(),
+            caught_up: BTreeSet::new(),
+        }
+    }
+}
+
+/// Where the watcher gets the latest posts from
+pub trait PostSource {
+    /// The latest posts. Sources can use what's `live` to tell how far back they need to go
+    fn posts(&mut self, live: &BTreeSet<Post>) -> Result<Listing, FetchError>;
+
+    /// What's left of the request budget as of the last fetch
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+
+    /// Whether posts dropping out of the listing says anything about them
+    fn tracks_expiry(&self) -> bool {
+        true
+    }
+
+    /// Whether the source has run out of new listings. Live sources never do
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+/// Why fetching posts failed. Each kind gets backed off from a bit differently
+#[derive(Debug)]
+pub enum FetchError {
+    /// Reddit stopped accepting our auth. We log in again on the next attempt
+    Auth(String),
+    /// Reddit wants us to slow down, possibly saying for how long
+    RateLimited { retry_after: Option<Duration> },
+    /// Reddit is having a bad time on its end
+    Server(StatusCode),
+    /// We couldn't reach reddit
+    Network(String),
+    /// Anything else e.g. a subreddit that doesn't exist or a listing we can't make sense of
+    Unexpected(String),
+}
+
+impl FetchError {
+    pub fn from_response(response: &Response) -> Option<Self> {
+        let status = response.status();
+        let error = match status {
+            _ if status.is_success() => return None,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Self::Auth(format!("Rejected with {status}"))
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                let headers = response.headers();
+                let retry_after = header_secs(headers, RETRY_AFTER)
+                    .or_else(|| header_secs(headers, "x-ratelimit-reset"));
+                Self::RateLimited { retry_after }
+            }
+            _ if status.is_server_error() => Self::Server(status),
+            _ => Self::Unexpected(format!("Unexpected status {status}")),
+        };
+
+        Some(error)
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auth(reason) => write!(f, "Auth failed: {reason}"),
+            Self::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(f, "Rate limited for {}s", retry_after.as_secs()),
+            Self::RateLimited { retry_after: None } => f.write_str("Rate limited"),
+            Self::Server(status) => write!(f, "Server error: {status}"),
+            Self::Network(error) => write!(f, "Network error: {error}"),
+            Self::Unexpected(error) => write!(f, "Unexpected error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<RouxError> for FetchError {
+    fn from(error: RouxError) -> Self {
+        match error {
+            RouxError::Status(response) => Self::from_response(&response)
+                .unwrap_or_else(|| Self::Unexpected("Failed with a successful status".to_owned())),
+            RouxError::Network(error) => error.into(),
+            RouxError::Parse(error) => Self::Unexpected(error.to_string()),
+            RouxError::Auth(reason) => Self::Auth(reason),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            Self::Unexpected(error.to_string())
+        } else {
+            Self::Network(error.to_string())
+        }
+    }
+}
+
+/// What reddit says is left of our request budget
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub remaining: f32,
+    /// Until the budget gets reset
+    pub reset: Duration,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let remaining = headers
+            .get("x-ratelimit-remaining")?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()?;
+        let reset = header_secs(headers, "x-ratelimit-reset")?;
+        Some(Self { remaining, reset })
+    }
+}
+
+fn header_secs(headers: &HeaderMap, name: impl AsHeaderName) -> Option<Duration> {
+    let secs = headers.get(name)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// A client that will log in as the bot when used
+pub fn client() -> roux::Reddit {
+    let secrets = crate::config::expect_secrets();
+    let user_agent = concat!(
+        "AutoShadow0133:",
+        env!("CARGO_PKG_VERSION"),
+        " from https://github.com/CosmicHorrorDev/auto_shadow0133"
+    );
+    roux::Reddit::new(
+        user_agent,
+        &secrets.reddit.client_id,
+        &secrets.reddit.client_secret,
+    )
+    .username(&secrets.reddit.username)
+    .password(&secrets.reddit.password)
+}
+
+/// Makes authed requests to reddit's API
+///
+/// roux's listings don't check the status or expose the rate-limit headers, so we make requests
+/// ourselves with its authed client
+#[derive(Default)]
+struct Api {
+    // Logged in lazily, and again whenever reddit stops accepting our auth
+    maybe_me: Option<Me>,
+    maybe_rate_limit: Option<RateLimit>,
+}
+
+impl Api {
+    /// The body of a successful response to `GET /{path}`
+    fn get(&mut self, path: &str, query: &[(&str, String)]) -> Result<String, FetchError> {
+        let me = match self.maybe_me.take() {
+            Some(me) => me,
+            None => {
+                tracing::info!("Logging in");
+                client().login()?
+            }
+        };
+        let me = self.maybe_me.insert(me);
+
+        let response = me
+            .client
+            .get(format!("https://oauth.reddit.com/{path}"))
+            .query(query)
+            .send()?;
+
+        self.maybe_rate_limit = RateLimit::from_headers(response.headers());
+        if let Some(error) = FetchError::from_response(&response) {
+            if let FetchError::Auth(_) = error {
+                self.maybe_me = None;
+            }
+            return Err(error);
+        }
+
+        Ok(response.text()?)
+    }
+}
+
+struct SubredditFeed {
+    name: String,
+    api: Api,
+    max_pages: u32,
+    num_gaps: u32,
+    maybe_recorder: Option<Recorder>,
+}
+
+impl SubredditFeed {
+    fn new(name: &str, settings: &WatcherSettings, maybe_recorder: Option<Recorder>) -> Self {
+        Self {
+            name: name.to_owned(),
+            api: Api::default(),
+            max_pages: settings.max_pages,
+            num_gaps: 0,
+            maybe_recorder,
+        }
+    }
+
+    fn fetch_page(&mut self, after: Option<&str>) -> Result<Page, FetchError> {
+        let mut query = vec![("limit", NUM_LATEST_POSTS.to_string())];
+        if let Some(after) = after {
+            query.push(("after", after.to_owned()));
+        }
+        let text = self.api.get(&format!("r/{}/new", self.name), &query)?;
+
+        let listing: Submissions = serde_json::from_str(&text)
+            .map_err(|error| FetchError::Unexpected(format!("Invalid listing: {error}")))?;
+        let posts = listing
+            .data
+            .children
+            .into_iter()
+            .map(|container| container.data)
+            .collect();
+        Ok((posts, listing.data.after))
+    }
+}
+
+impl PostSource for SubredditFeed {
+    fn posts(&mut self, live: &BTreeSet<Post>) -> Result<Listing, FetchError> {
+        let max_pages = self.max_pages;
+        let Paginated {
+            latest,
+            caught_up,
+            gap,
+        } = paginate(|after| self.fetch_page(after), live, max_pages)?;
+        if gap {
+            self.num_gaps += 1;
+            tracing::warn!(
+                subreddit = self.name,
+                max_pages = self.max_pages,
+                num_gaps = self.num_gaps,
+                "Didn't reach any already seen posts. Some posts were likely missed"
+            );
+        }
+
+        let snapshot = Snapshot {
+            fetched_at: Some(OffsetDateTime::now_utc().unix_timestamp()),
+            posts: latest,
+            caught_up,
+        };
+        if let Some(recorder) = &self.maybe_recorder {
+            if let Err(error) = recorder.record(&self.name, &snapshot) {
+                tracing::warn!(%error, subreddit = self.name, "Failed recording listing");
+            }
+        }
+
+        Ok(snapshot.into())
+    }
+
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.api.maybe_rate_limit.as_ref()
+    }
+}
+
+/// A page of posts along with the fullname to fetch the following page with
+type Page = (Vec<SubmissionData>, Option<String>);
+
+struct Paginated {
+    /// The first page
+    latest: Vec<SubmissionData>,
+    /// Every page after the first
+    caught_up: Vec<SubmissionData>,
+    /// Whether we gave up before reaching any posts that were already live
+    gap: bool,
+}
+
+/// Pages back through the listing until reaching a post that's already live, the end of the
+/// listing, or `max_pages`
+fn paginate<F>(
+    mut fetch_page: F,
+    live: &BTreeSet<Post>,
+    max_pages: u32,
+) -> Result<Paginated, FetchError>
+where
+    F: FnMut(Option<&str>) -> Result<Page, FetchError>,
+{
+    let live_ids: BTreeSet<_> = live.iter().map(|post| post.id.as_str()).collect();
+    let mut latest = None;
+    let mut caught_up = Vec::new();
+    let mut after = None;
+    for _ in 0..max_pages.max(1) {
+        let (page, maybe_next) = fetch_page(after.as_deref())?;
+        // Nothing to catch up with when we're just starting out
+        let reached_live =
+            live_ids.is_empty() || page.iter().any(|post| live_ids.contains(post.id.as_str()));
+        match latest {
+            None => latest = Some(page),
+            Some(_) => caught_up.extend(page),
+        }
+
+        match maybe_next {
+            Some(next) if !reached_live => after = Some(next),
+            _ => {
+                return Ok(Paginated {
+                    latest: latest.unwrap_or_default(),
+                    caught_up,
+                    gap: false,
+                })
+            }
+        }
+    }
+
+    Ok(Paginated {
+        latest: latest.unwrap_or_default(),
+        caught_up,
+        gap: true,
+    })
+}
+
+const NUM_LATEST_POSTS: u32 = 20;
+const ID_BUFFER: usize = NUM_LATEST_POSTS as usize + 300;
+
+/// Everything the watcher needs to pick back up where it left off after a restart
+#[derive(Debug, Default)]
+pub struct WatcherState {
+    pub live: BTreeSet<Post>,
+    // Posts being removed can cause already fresh/expired posts to be re-emitted. Keep track of a
+    // longer queue to keep from emitting more than once (unless a ridiculous amount of posts are
+    // removed)
+    pub fresh_debounce: VecDeque<SmallString>,
+    pub expired_debounce: VecDeque<SmallString>,
+}
+
+pub struct Watcher {
+    source: Box<dyn PostSource>,
+    state: WatcherState,
+    scheduler: Scheduler,
+    next_poll: Instant,
+}
+
+impl Watcher {
+    pub fn new(
+        subreddit: &str,
+        state: WatcherState,
+        settings: &WatcherSettings,
+        maybe_recorder: Option<Recorder>,
+    ) -> Self {
+        let source = SubredditFeed::new(subreddit, settings, maybe_recorder);
+        Self::with_source(Box::new(source), state, settings)
+    }
+
+    /// Watches the subreddit's reports queue instead of its latest posts
+    pub fn for_reports(subreddit: &str, state: WatcherState, settings: &WatcherSettings) -> Self {
+        Self::with_source(Box::new(ReportsFeed::new(subreddit)), state, settings)
+    }
+
+    pub fn with_source(
+        source: Box<dyn PostSource>,
+        state: WatcherState,
+        settings: &WatcherSettings,
+    ) -> Self {
+        Self {
+            source,
+            state,
+            scheduler: Scheduler::new(settings),
+            next_poll: Instant::now(),
+        }
+    }
+
+    /// When the watcher should next be updated
+    pub fn next_poll(&self) -> Instant {
+        self.next_poll
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_poll
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.source.is_done()
+    }
+
+    pub fn state(&self) -> &WatcherState {
+        &self.state
+    }
+
+    pub fn update(&mut self) -> Update {
+        fn update_post_listing(
+            left: &BTreeSet<Post>,
+            right: &BTreeSet<Post>,
+            debounce_ids: &mut VecDeque<SmallString>,
+        ) -> Vec<Post> {
+            let diff: Vec<_> = left
+                .difference(right)
+                .filter(|post| !debounce_ids.contains(&post.id))
+                .cloned()
+                .collect();
+
+            for post in &diff {
+                // There shouldn't ever be more ids than `ID_BUFFER`, but loop just to be safe
+                while debounce_ids.len() >= ID_BUFFER {
+                    debounce_ids.pop_back();
+                }
+
+                debounce_ids.push_front(post.id.clone());
+            }
+
+            diff
+        }
+
+        let maybe_latest = self.source.posts(&self.state.live);
+        let tracks_expiry = self.source.tracks_expiry();
+        self.next_poll = self
+            .scheduler
+            .next_poll(&maybe_latest, self.source.rate_limit());
+
+        let WatcherState {
+            live,
+            fresh_debounce,
+            expired_debounce,
+        } = &mut self.state;
+        match maybe_latest {
+            Ok(Listing { latest, caught_up }) => {
+                // Find posts that are newly included in `.lastest()`. Posts that were caught up on
+                // are just as new, but don't become live
+                let new: BTreeSet<_> = latest.union(&caught_up).cloned().collect();
+                let mut fresh = update_post_listing(&new, live, fresh_debounce);
+                // Posts that pick up more reports while they're live get another look too. Fewer
+                // reports can't make a post look any worse, so those are left alone
+                let num_reports =
+                    |post: &Post| -> u32 { post.reports.iter().map(|report| report.count).sum() };
+                fresh.extend(
+                    latest
+                        .iter()
+                        .filter(|post| {
+                            live.get(*post)
+                                .is_some_and(|prev| num_reports(post) > num_reports(prev))
+                        })
+                        .cloned(),
+                );
+                // Find posts that were in `.latest()`, but aren't now
+                let mut expired = update_post_listing(live, &latest, expired_debounce);
+                if !tracks_expiry {
+                    expired.clear();
+                }
+                // Posts normally get pushed out by newer ones. Anything newer than the oldest post
+                // that's still around must have been taken down instead
+                if let Some(oldest) = latest.iter().map(|post| post.created).min() {
+                    for post in &mut expired {
+                        if post.created > oldest {
+                            post.removed = true;
+                        }
+                    }
+                }
+
+                // Update stored data
+                *live = latest;
+
+                let update = Update { fresh, expired };
+                if !update.is_empty() {
+                    // HACK: hopefully tracing gets support for pretty-debug sometime
+                    tracing::debug!(update = %format!("{update:#?}"), "Emitting update");
+
+                    let log_format_titles = |posts: &[Post]| {
+                        let titles: Vec<_> = posts
+                            .iter()
+                            .map(|post| utils::truncate_str(&post.title, 80))
+                            .collect();
+                        format!("{titles:#?}")
+                    };
+                    tracing::info!(
+                        fresh = %log_format_titles(&update.fresh),
+                        expired = %log_format_titles(&update.expired),
+                        "Update summary"
+                    );
+                }
+                update
+            }
+            // Already logged above
+            Err(_) => Update::default(),
+        }
+    }
+}
+
+impl From<SubmissionData> for Post {
+    fn from(
+        SubmissionData {
+            id,
+            author,
+            score,
+            title,
+            selftext,
+            created_utc,
+            url: mut maybe_url,
+            banned_by,
+            subreddit,
+            ..
+        }: SubmissionData,
+    ) -> Self {
+        let created = OffsetDateTime::from_unix_timestamp(created_utc as i64).unwrap();
+        let selftext = selftext.trim();
+        let selftext = (!selftext.is_empty()).then_some(selftext);
+        let title = title.trim();
+
+        if maybe_url.is_none() {
+            // Some people mess up link posting, so if the title is a link and there is no body, or
+            // there is a title, but the body is just a link then consider it a link post
+            let check_as_url = selftext.unwrap_or(title);
+            if Url::parse(check_as_url).is_ok() {
+                maybe_url = Some(check_as_url.to_owned());
+            }
+        }
+
+        maybe_url = maybe_url.map(|url| {
+            // Resolve crossposts (starting with /r/ or /user/)
+            if url.starts_with("/") {
+                format!("https://reddit.com{url}")
+            } else {
+                url
+            }
+        });
+
+        // URL parsing is pretty permissive, so sanity check to remove garbo
+        if let Some(url) = &maybe_url {
+            match Url::parse(url) {
+                Ok(parsed) => {
+                    if !["http", "https"].contains(&parsed.scheme()) || url.contains(' ') {
+                        maybe_url = None;
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(%error, %url, "Valid URL failed parsing!");
+                    maybe_url = None;
+                }
+            }
+        }
+
+        Self {
+            id: SmallString::from(id),
+            author: SmallString::from(author),
+            score,
+            title: title.to_owned(),
+            created,
+            body: selftext.map(ToOwned::to_owned),
+            link: maybe_url,
+            category: None,
+            removed: banned_by.is_some(),
+            subreddit: SmallString::from(subreddit),
+            reports: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    pub(super) fn submission(id: &str, created_utc: i64) -> serde_json::Value {
+        json!({
+            "id": id,
+            "name": format!("t3_{id}"),
+            "author": "someone",
+            "title": format!("Post {id}"),
+            "selftext": "",
+            "subreddit": "rust",
+            "subreddit_id": "t5_2s7lj",
+            "permalink": format!("/r/rust/comments/{id}/"),
+            "thumbnail": "self",
+            "score": 1.0,
+            "ups": 1.0,
+            "downs": 0.0,
+            "upvote_ratio": 1.0,
+            "gilded": 0,
+            "num_comments": 0,
+            "created": created_utc as f64,
+            "created_utc": created_utc as f64,
+            "edited": false,
+            "archived": false,
+            "clicked": false,
+            "hidden": false,
+            "hide_score": false,
+            "is_self": true,
+            "locked": false,
+            "over_18": false,
+            "quarantine": false,
+            "saved": false,
+            "spoiler": false,
+            "stickied": false,
+            "visited": false,
+        })
+    }
+
+    fn ids(posts: &[Post]) -> Vec<&str> {
+        posts.iter().map(|post| post.id.as_str()).collect()
+    }
+
+    #[test]
+    fn replay() {
+        let (a, b, c, d) = (
+            submission("a", 1),
+            submission("b", 2),
+            submission("c", 3),
+            submission("d", 4),
+        );
+        let snapshots = [
+            vec![&a, &b],
+            vec![&a, &b, &c],
+            // `a` gets pushed out by `d`
+            vec![&b, &c, &d],
+            // `c` gets removed
+            vec![&b, &d],
+            // and then reapproved
+            vec![&b, &c, &d],
+        ];
+        let lines: Vec<_> = snapshots
+            .iter()
+            .map(|posts| json!({ "posts": posts }).to_string())
+            .collect();
+        let source = ReplaySource::from_reader(lines.join("\n").as_bytes()).unwrap();
+        let mut watcher = Watcher::with_source(
+            Box::new(source),
+            WatcherState::default(),
+            &WatcherSettings::default(),
+        );
+
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["a", "b"]);
+        assert!(update.expired.is_empty());
+
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["c"]);
+        assert!(update.expired.is_empty());
+
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["d"]);
+        assert_eq!(ids(&update.expired), ["a"]);
+        assert!(!update.expired[0].removed);
+
+        let update = watcher.update();
+        assert!(update.fresh.is_empty());
+        assert_eq!(ids(&update.expired), ["c"]);
+        assert!(update.expired[0].removed);
+
+        // Already handled posts don't get emitted again
+        assert!(!watcher.is_done());
+        let update = watcher.update();
+        assert!(update.is_empty());
+        assert!(watcher.is_done());
+
+        // Running out leaves things as they are
+        assert!(watcher.update().is_empty());
+        let live: Vec<_> = watcher
+            .state()
+            .live
+            .iter()
+            .map(|post| post.id.as_str())
+            .collect();
+        assert_eq!(live, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn pagination() {
+        // Newest first like reddit returns them
+        let page = |ids: &[&str], maybe_next: Option<&str>| {
+            let posts = ids
+                .iter()
+                .map(|id| serde_json::from_value(submission(id, 1)).unwrap())
+                .collect();
+            (posts, maybe_next.map(ToOwned::to_owned))
+        };
+        let fake_listing = |pages: Vec<Page>| {
+            let mut pages = pages.into_iter();
+            move |_after: Option<&str>| Ok(pages.next().expect("Fetched too many pages"))
+        };
+        let live = |ids: &[&str]| -> BTreeSet<Post> {
+            ids.iter()
+                .map(|id| {
+                    Post::from(serde_json::from_value::<SubmissionData>(submission(id, 1)).unwrap())
+                })
+                .collect()
+        };
+        let ids = |paginated: &Paginated| -> Vec<String> {
+            paginated
+                .latest
+                .iter()
+                .chain(&paginated.caught_up)
+                .map(|post| post.id.clone())
+                .collect()
+        };
+
+        // Just the first page when starting out
+        let fetch = fake_listing(vec![page(&["e", "d"], Some("t3_d"))]);
+        let paginated = paginate(fetch, &live(&[]), 3).unwrap();
+        assert_eq!(ids(&paginated), ["e", "d"]);
+        assert!(!paginated.gap);
+
+        // Keeps going until it reaches a live post
+        let fetch = fake_listing(vec![
+            page(&["e", "d"], Some("t3_d")),
+            page(&["c", "b"], Some("t3_b")),
+        ]);
+        let paginated = paginate(fetch, &live(&["b", "a"]), 3).unwrap();
+        assert_eq!(ids(&paginated), ["e", "d", "c", "b"]);
+        assert_eq!(paginated.latest.len(), 2);
+        assert!(!paginated.gap);
+
+        // or the end of the listing
+        let fetch = fake_listing(vec![page(&["e", "d"], Some("t3_d")), page(&["c"], None)]);
+        let paginated = paginate(fetch, &live(&["a"]), 3).unwrap();
+        assert_eq!(ids(&paginated), ["e", "d", "c"]);
+        assert!(!paginated.gap);
+
+        // Running out of pages means there's a gap
+        let fetch = fake_listing(vec![
+            page(&["e", "d"], Some("t3_d")),
+            page(&["c", "b"], Some("t3_b")),
+        ]);
+        let paginated = paginate(fetch, &live(&["a"]), 2).unwrap();
+        assert_eq!(ids(&paginated), ["e", "d", "c", "b"]);
+        assert!(paginated.gap);
+    }
+
+    #[test]
+    fn catching_up() {
+        let (a, b, c, d) = (
+            submission("a", 1),
+            submission("b", 2),
+            submission("c", 3),
+            submission("d", 4),
+        );
+        let lines = [
+            json!({ "posts": [&a] }),
+            // Fell behind, so `b` had to be caught up on from the next page
+            json!({ "posts": [&c, &d], "caught_up": [&b, &a] }),
+            json!({ "posts": [&c, &d] }),
+        ]
+        .map(|line| line.to_string());
+        let source = ReplaySource::from_reader(lines.join("\n").as_bytes()).unwrap();
+        let mut watcher = Watcher::with_source(
+            Box::new(source),
+            WatcherState::default(),
+            &WatcherSettings::default(),
+        );
+
+        assert_eq!(ids(&watcher.update().fresh), ["a"]);
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["b", "c", "d"]);
+        // `a` got pushed out of the latest posts
+        assert_eq!(ids(&update.expired), ["a"]);
+        // and the caught up posts never went live, so they don't expire with early scores
+        assert!(watcher.update().is_empty());
+    }
+
+    /// Hands out canned results in order
+    struct FlakySource(VecDeque<Result<Vec<&'static str>, FetchError>>);
+
+    impl PostSource for FlakySource {
+        fn posts(&mut self, _live: &BTreeSet<Post>) -> Result<Listing, FetchError> {
+            let ids = self.0.pop_front().expect("Polled too many times")?;
+            let posts = ids
+                .into_iter()
+                .map(|id| {
+                    let submission: SubmissionData =
+                        serde_json::from_value(submission(id, 1)).unwrap();
+                    Post::from(submission)
+                })
+                .collect();
+            Ok(posts)
+        }
+    }
+
+    #[test]
+    fn failures() {
+        let settings = WatcherSettings {
+            poll_interval_sec: 60,
+            max_backoff_sec: 3600,
+            ..Default::default()
+        };
+        let results = vec![
+            Ok(vec!["a", "b"]),
+            Err(FetchError::Server(StatusCode::BAD_GATEWAY)),
+            Err(FetchError::Network("connection reset".to_owned())),
+            Err(FetchError::RateLimited {
+                retry_after: Some(Duration::from_secs(3000)),
+            }),
+            Ok(vec!["b", "c"]),
+        ];
+        let source = FlakySource(results.into());
+        let mut watcher =
+            Watcher::with_source(Box::new(source), WatcherState::default(), &settings);
+        let wait = |watcher: &Watcher| watcher.next_poll().duration_since(Instant::now());
+
+        assert!(watcher.is_due());
+        assert_eq!(ids(&watcher.update().fresh), ["a", "b"]);
+        assert!(!watcher.is_due());
+        assert!(wait(&watcher) <= Duration::from_secs(60));
+
+        // Failures back off (with jitter) without touching what's live
+        for max_sec in [60, 120] {
+            assert!(watcher.update().is_empty());
+            let wait = wait(&watcher);
+            assert!(Duration::from_secs(max_sec / 2 - 1) <= wait, "{wait:?}");
+            assert!(wait <= Duration::from_secs(max_sec), "{wait:?}");
+        }
+        // Reddit asking us to wait trumps the backoff
+        assert!(watcher.update().is_empty());
+        assert!(wait(&watcher) > Duration::from_secs(2990));
+        assert_eq!(watcher.state().live.len(), 2);
+
+        // and things pick back up where they left off after
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["c"]);
+        assert_eq!(ids(&update.expired), ["a"]);
+        assert!(wait(&watcher) <= Duration::from_secs(60));
+    }
+
+    /// Hands out listings of posts with their number of reports like the reports queue
+    struct FakeReports(VecDeque<Vec<(&'static str, u32)>>);
+
+    impl PostSource for FakeReports {
+        fn posts(&mut self, _live: &BTreeSet<Post>) -> Result<Listing, FetchError> {
+            let reported = self.0.pop_front().expect("Polled too many times");
+            let posts = reported
+                .into_iter()
+                .map(|(id, count)| {
+                    let submission: SubmissionData =
+                        serde_json::from_value(submission(id, 1)).unwrap();
+                    let report = crate::types::Report {
+                        reason: "This is about the game".to_owned(),
+                        count,
+                    };
+                    Post {
+                        reports: vec![report],
+                        ..Post::from(submission)
+                    }
+                })
+                .collect();
+            Ok(posts)
+        }
+
+        fn tracks_expiry(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn reports_queue() {
+        let listings = vec![
+            vec![("a", 1)],
+            vec![("a", 1), ("b", 1)],
+            vec![("a", 2)],
+            vec![("a", 1)],
+            vec![],
+        ];
+        let mut watcher = Watcher::with_source(
+            Box::new(FakeReports(listings.into())),
+            WatcherState::default(),
+            &WatcherSettings::default(),
+        );
+
+        assert_eq!(ids(&watcher.update().fresh), ["a"]);
+        assert_eq!(ids(&watcher.update().fresh), ["b"]);
+        // More reports means another look, and handled posts leaving the queue is a non-event
+        let update = watcher.update();
+        assert_eq!(ids(&update.fresh), ["a"]);
+        assert_eq!(update.fresh[0].reports[0].count, 2);
+        assert!(update.expired.is_empty());
+        // Reports going away doesn't warrant another look
+        assert!(watcher.update().is_empty());
+        assert!(watcher.update().is_empty());
+    }
+
+    #[test]
+    fn bad_replay() {
+        let lines = format!("{}\nnot json", json!({ "posts": [submission("a", 1)] }));
+        let error = ReplaySource::from_reader(lines.as_bytes()).err().unwrap();
+        assert!(error.to_string().contains("line 2"), "{error}");
+    }
+}
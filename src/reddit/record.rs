@@ -0,0 +1,172 @@
+//! Saves the raw listings of subreddits' latest posts, so that they can be replayed later
+//!
+//! Listings get appended to a JSON lines file per subreddit per day. Only the latest posts are
+//! recorded. The reports queue and comments only get re-checked live
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    iter,
+    path::PathBuf,
+};
+
+use super::Snapshot;
+
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+/// Fields that identify the author besides `author` itself
+const IDENTIFYING_FIELDS: &[&str] = &["author_flair_text", "author_flair_css_class"];
+
+/// Fields with the name of the mod that handled the post. These get stand-ins instead of being
+/// dropped, since they're what says whether a post was removed or approved
+const MOD_FIELDS: &[&str] = &["approved_by", "banned_by"];
+
+#[derive(Clone)]
+pub struct Recorder {
+    dir: PathBuf,
+    maybe_redactor: Option<Redactor>,
+}
+
+impl Recorder {
+    pub fn new(dir: PathBuf, redact_authors: bool) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let maybe_redactor = redact_authors.then(Redactor::new).transpose()?;
+        Ok(Self {
+            dir,
+            maybe_redactor,
+        })
+    }
+
+    pub fn record(&self, subreddit: &str, snapshot: &Snapshot) -> anyhow::Result<()> {
+        let mut value = serde_json::to_value(snapshot)?;
+        if let Some(redactor) = &self.maybe_redactor {
+            for listing in ["posts", "caught_up"] {
+                let Some(posts) = value
+                    .get_mut(listing)
+                    .and_then(|posts| posts.as_array_mut())
+                else {
+                    continue;
+                };
+                for post in posts {
+                    for field in iter::once("author").chain(MOD_FIELDS.iter().copied()) {
+                        if let Some(name) = post[field].as_str() {
+                            post[field] = redactor.redact(name).into();
+                        }
+                    }
+                    for field in IDENTIFYING_FIELDS {
+                        if let Some(value) = post.get_mut(field) {
+                            *value = serde_json::Value::Null;
+                        }
+                    }
+                }
+            }
+        }
+
+        let fetched_at = snapshot
+            .fetched_at
+            .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+            .unwrap_or_else(OffsetDateTime::now_utc);
+        let path = self.path(subreddit, fetched_at);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{value}")?;
+
+        Ok(())
+    }
+
+    fn path(&self, subreddit: &str, fetched_at: OffsetDateTime) -> PathBuf {
+        let file_name = format!("{}-{}.jsonl", subreddit.to_lowercase(), fetched_at.date());
+        self.dir.join(file_name)
+    }
+}
+
+/// Swaps authors and mods for stand-ins, so that posts from the same author still line up
+///
+/// Stand-ins are hashed with a random key that only lives in memory. Otherwise they could be
+/// reversed by hashing known usernames. That means they only line up for as long as the recorder
+/// is around
+#[derive(Clone)]
+struct Redactor {
+    key: [u8; 32],
+}
+
+impl Redactor {
+    fn new() -> anyhow::Result<Self> {
+        let mut key = [0; 32];
+        getrandom::getrandom(&mut key)?;
+        Ok(Self { key })
+    }
+
+    fn redact(&self, username: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.key)
+            .chain_update(username)
+            .finalize();
+        let digest = format!("{digest:x}");
+        format!("redacted-{}", &digest[..12])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reddit::{tests::submission, PostSource, ReplaySource};
+
+    #[test]
+    fn record_and_replay() {
+        let dir =
+            std::env::temp_dir().join(format!("auto_shadow0133-record-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let recorder = Recorder::new(dir.clone(), true).unwrap();
+
+        let snapshot = |ids: &[&str]| {
+            let posts = ids
+                .iter()
+                .map(|id| {
+                    let mut data = submission(id, 1);
+                    data["author_flair_text"] = "Maintainer of some-crate".into();
+                    if *id == "b" {
+                        data["banned_by"] = "some-mod".into();
+                    }
+                    serde_json::from_value(data).unwrap()
+                })
+                .collect();
+            Snapshot {
+                // 2023-11-14
+                fetched_at: Some(1_700_000_000),
+                posts,
+                caught_up: Vec::new(),
+            }
+        };
+        recorder.record("Rust", &snapshot(&["a"])).unwrap();
+        recorder.record("Rust", &snapshot(&["a", "b"])).unwrap();
+
+        let path = dir.join("rust-2023-11-14.jsonl");
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(!text.contains("someone"));
+        assert!(!text.contains("some-crate"));
+        assert!(!text.contains("some-mod"));
+
+        let mut replay = ReplaySource::open(&path).unwrap();
+        let mut authors = Vec::new();
+        while !replay.is_done() {
+            let listing = replay.posts(&Default::default()).unwrap();
+            for post in listing.latest {
+                // Removals survive redaction
+                assert_eq!(post.removed, post.id == "b");
+                authors.push(post.author);
+            }
+        }
+        assert_eq!(authors.len(), 3);
+        let redactor = recorder.maybe_redactor.as_ref().unwrap();
+        assert!(authors
+            .iter()
+            .all(|author| author == &redactor.redact("someone")));
+        // Other recorders get their own stand-ins
+        let other = Redactor::new().unwrap();
+        assert_ne!(other.redact("someone"), redactor.redact("someone"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
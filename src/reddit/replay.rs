@@ -0,0 +1,91 @@
+//! Feeds previously recorded listings back through the watcher, so that incidents can be
+//! reproduced offline
+//!
+//! Each line of a replay file is a JSON [`Snapshot`] of a single fetch
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use super::{FetchError, Listing, PostSource};
+use crate::types::Post;
+
+use anyhow::Context as _;
+use roux::submission::SubmissionData;
+use serde::{Deserialize, Serialize};
+
+/// The posts from a single fetch of a subreddit's latest posts
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    /// Unix timestamp of when the listing was fetched
+    #[serde(default)]
+    pub fetched_at: Option<i64>,
+    pub posts: Vec<SubmissionData>,
+    /// Older posts from any pages past the first that were fetched to catch up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub caught_up: Vec<SubmissionData>,
+}
+
+impl From<Snapshot> for Listing {
+    fn from(snapshot: Snapshot) -> Self {
+        let to_posts = |submissions: Vec<SubmissionData>| -> BTreeSet<Post> {
+            submissions.into_iter().map(Post::from).collect()
+        };
+        Self {
+            latest: to_posts(snapshot.posts),
+            caught_up: to_posts(snapshot.caught_up),
+        }
+    }
+}
+
+pub struct ReplaySource {
+    snapshots: VecDeque<Listing>,
+    last: BTreeSet<Post>,
+}
+
+impl ReplaySource {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed opening replay file {}", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut snapshots = VecDeque::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let snapshot: Snapshot = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid snapshot on line {}", i + 1))?;
+            snapshots.push_back(snapshot.into());
+        }
+
+        Ok(Self {
+            snapshots,
+            last: BTreeSet::new(),
+        })
+    }
+}
+
+impl PostSource for ReplaySource {
+    fn posts(&mut self, _live: &BTreeSet<Post>) -> Result<Listing, FetchError> {
+        // Keep repeating the final listing once we run out, so that nothing looks like it expired
+        match self.snapshots.pop_front() {
+            Some(listing) => {
+                self.last = listing.latest.clone();
+                Ok(listing)
+            }
+            None => Ok(self.last.iter().cloned().collect()),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
@@ -0,0 +1,146 @@
+//! Watches a subreddit's reports queue, so that spam that slipped past and got reported by users
+//! gets another look with the report reasons in hand
+//!
+//! Posts get re-checked when they first show up in the queue, and again whenever they pick up more
+//! reports while they're still in it. Losing reports doesn't count. Neither does a post leaving the
+//! queue (like after a mod approves it) and coming back later, since the watcher's debounce treats
+//! it as already seen
+
+use std::collections::BTreeSet;
+
+use super::{Api, FetchError, Listing, PostSource, RateLimit};
+use crate::types::{Post, Report};
+
+use roux::{response::BasicListing, submission::SubmissionData};
+use serde::Deserialize;
+
+/// The most reported posts that reddit will hand out in one go
+const NUM_REPORTED_POSTS: u32 = 100;
+
+pub struct ReportsFeed {
+    name: String,
+    api: Api,
+}
+
+impl ReportsFeed {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            api: Api::default(),
+        }
+    }
+}
+
+impl PostSource for ReportsFeed {
+    fn posts(&mut self, _live: &BTreeSet<Post>) -> Result<Listing, FetchError> {
+        let query = [
+            ("only", "links".to_owned()),
+            ("limit", NUM_REPORTED_POSTS.to_string()),
+        ];
+        let text = self
+            .api
+            .get(&format!("r/{}/about/reports", self.name), &query)?;
+        parse_listing(&text).map(Listing::from_iter)
+    }
+
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.api.maybe_rate_limit.as_ref()
+    }
+
+    // Posts leave the queue whenever a mod handles them, approved or not
+    fn tracks_expiry(&self) -> bool {
+        false
+    }
+}
+
+/// A listing entry along with its user reports, which roux skips over
+#[derive(Deserialize)]
+struct ReportedSubmission {
+    #[serde(flatten)]
+    submission: SubmissionData,
+    /// Each report is a `[reason, count, ...]` array
+    #[serde(default)]
+    user_reports: Vec<Vec<serde_json::Value>>,
+}
+
+/// Posts from the listing that users reported. Ones that were only reported by mods (like us) are
+/// left out
+fn parse_listing(text: &str) -> Result<BTreeSet<Post>, FetchError> {
+    let listing: BasicListing<ReportedSubmission> = serde_json::from_str(text)
+        .map_err(|error| FetchError::Unexpected(format!("Invalid reports listing: {error}")))?;
+
+    let posts = listing
+        .data
+        .children
+        .into_iter()
+        .filter_map(|child| {
+            let ReportedSubmission {
+                submission,
+                user_reports,
+            } = child.data;
+            let reports: Vec<_> = user_reports
+                .iter()
+                .filter_map(|raw| parse_report(raw))
+                .collect();
+            (!reports.is_empty()).then(|| Post {
+                reports,
+                ..Post::from(submission)
+            })
+        })
+        .collect();
+    Ok(posts)
+}
+
+fn parse_report(raw: &[serde_json::Value]) -> Option<Report> {
+    let reason = raw.first()?.as_str()?.to_owned();
+    let count = raw.get(1)?.as_u64()?.try_into().ok()?;
+    Some(Report { reason, count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reddit::tests::submission;
+
+    use serde_json::json;
+
+    #[test]
+    fn reports_listing() {
+        let reported = |id, user_reports| {
+            let mut data = submission(id, 1);
+            data["user_reports"] = user_reports;
+            json!({ "kind": "t3", "data": data })
+        };
+        let listing = json!({
+            "kind": "Listing",
+            "data": {
+                "after": null,
+                "before": null,
+                "children": [
+                    reported("a", json!([["This is about the game", 3, false, false], ["Spam", 1]])),
+                    // Only reported by a mod
+                    reported("b", json!([])),
+                    reported("c", json!([[null, 1]])),
+                ],
+            },
+        });
+
+        let posts = parse_listing(&listing.to_string()).unwrap();
+        let ids: Vec<_> = posts.iter().map(|post| post.id.as_str()).collect();
+        assert_eq!(ids, ["a"]);
+        let reports = &posts.first().unwrap().reports;
+        assert_eq!(
+            reports,
+            &[
+                Report {
+                    reason: "This is about the game".to_owned(),
+                    count: 3,
+                },
+                Report {
+                    reason: "Spam".to_owned(),
+                    count: 1,
+                },
+            ]
+        );
+    }
+}
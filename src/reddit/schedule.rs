@@ -0,0 +1,158 @@
+//! Decides when to poll next based off of how the last poll went
+//!
+//! Successful polls wait the normal interval (or longer if we're about to run out of requests).
+//! Failures back off exponentially with some jitter, so that we're not hammering reddit while it's
+//! having a bad time
+
+use std::time::{Duration, Instant};
+
+use super::{FetchError, RateLimit};
+use crate::config::WatcherSettings;
+
+pub struct Scheduler {
+    poll_interval: Duration,
+    max_backoff: Duration,
+    /// The most requests that a single poll can take
+    requests_per_poll: f32,
+    num_failures: u32,
+    rng: fastrand::Rng,
+}
+
+impl Scheduler {
+    pub fn new(settings: &WatcherSettings) -> Self {
+        Self::with_rng(settings, fastrand::Rng::new())
+    }
+
+    fn with_rng(settings: &WatcherSettings, rng: fastrand::Rng) -> Self {
+        Self {
+            poll_interval: Duration::from_secs(settings.poll_interval_sec),
+            max_backoff: Duration::from_secs(settings.max_backoff_sec),
+            requests_per_poll: settings.max_pages.max(1) as f32,
+            num_failures: 0,
+            rng,
+        }
+    }
+
+    /// When to poll next given how the last poll went. Failures get logged along with the wait
+    pub fn next_poll<T>(
+        &mut self,
+        result: &Result<T, FetchError>,
+        maybe_rate_limit: Option<&RateLimit>,
+    ) -> Instant {
+        let delay = match result {
+            Ok(_) => self.on_success(maybe_rate_limit),
+            Err(error) => {
+                let delay = self.on_failure(error, maybe_rate_limit);
+                let num_failures = self.num_failures;
+                let retry_in_sec = delay.as_secs();
+                match error {
+                    FetchError::Auth(_) | FetchError::Unexpected(_) => {
+                        tracing::error!(%error, num_failures, retry_in_sec, "Failed fetching")
+                    }
+                    _ => tracing::warn!(%error, num_failures, retry_in_sec, "Failed fetching"),
+                }
+                delay
+            }
+        };
+
+        Instant::now() + delay
+    }
+
+    pub fn on_success(&mut self, maybe_rate_limit: Option<&RateLimit>) -> Duration {
+        self.num_failures = 0;
+        self.poll_interval
+            .max(self.rate_limit_wait(maybe_rate_limit))
+    }
+
+    pub fn on_failure(
+        &mut self,
+        error: &FetchError,
+        maybe_rate_limit: Option<&RateLimit>,
+    ) -> Duration {
+        self.num_failures = self.num_failures.saturating_add(1);
+
+        // Double the wait for each failure in a row up to the max
+        let exponent = (self.num_failures - 1).min(16);
+        let backoff = self
+            .poll_interval
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        // Wait anywhere from half to all of the backoff, so that retries don't line up
+        let backoff = backoff.mul_f32(0.5 + self.rng.f32() * 0.5);
+
+        let requested = match error {
+            FetchError::RateLimited {
+                retry_after: Some(retry_after),
+            } => *retry_after,
+            _ => Duration::ZERO,
+        };
+        backoff
+            .max(requested)
+            .max(self.rate_limit_wait(maybe_rate_limit))
+    }
+
+    /// Wait out the rest of the rate limit window when there aren't enough requests left for
+    /// another poll
+    fn rate_limit_wait(&self, maybe_rate_limit: Option<&RateLimit>) -> Duration {
+        match maybe_rate_limit {
+            Some(RateLimit { remaining, reset }) if *remaining < self.requests_per_poll => *reset,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let settings = WatcherSettings {
+            poll_interval_sec: 60,
+            max_backoff_sec: 600,
+            max_pages: 2,
+        };
+        let mut scheduler = Scheduler::with_rng(&settings, fastrand::Rng::with_seed(0));
+        let secs = Duration::from_secs;
+        let network = FetchError::Network("connection reset".to_owned());
+
+        assert_eq!(scheduler.on_success(None), secs(60));
+
+        // Jittered, but growing
+        let expected_ranges = [
+            (30, 60),
+            (60, 120),
+            (120, 240),
+            (240, 480),
+            (300, 600),
+            (300, 600),
+        ];
+        for (min, max) in expected_ranges {
+            let delay = scheduler.on_failure(&network, None);
+            assert!(secs(min) <= delay && delay <= secs(max), "{delay:?}");
+        }
+        assert_eq!(scheduler.num_failures, 6);
+
+        // Success resets things
+        assert_eq!(scheduler.on_success(None), secs(60));
+        assert_eq!(scheduler.num_failures, 0);
+
+        // Reddit telling us to wait trumps the backoff
+        let rate_limited = FetchError::RateLimited {
+            retry_after: Some(secs(500)),
+        };
+        assert_eq!(scheduler.on_failure(&rate_limited, None), secs(500));
+
+        // and so does running low on requests
+        let plenty = RateLimit {
+            remaining: 100.0,
+            reset: secs(300),
+        };
+        assert_eq!(scheduler.on_success(Some(&plenty)), secs(60));
+        let running_out = RateLimit {
+            remaining: 1.0,
+            reset: secs(300),
+        };
+        assert_eq!(scheduler.on_success(Some(&running_out)), secs(300));
+    }
+}
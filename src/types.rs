@@ -1,9 +1,11 @@
-use std::{cmp::Ordering, fmt};
+use std::{cmp::Ordering, fmt, sync::OnceLock};
 
 use crate::utils;
 
 use diesel_derive_enum::DbEnum;
 use pulldown_cmark::{CodeBlockKind, Event, LinkType, Tag};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use smartstring::alias::String as SmallString;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
@@ -19,20 +21,83 @@ pub struct Post {
     pub body: Option<String>,
     pub link: Option<String>,
     pub category: Option<Category>,
+    /// Whether a moderator took the post down
+    pub removed: bool,
+    pub subreddit: SmallString,
+    /// What users reported the post for. Only filled in for posts from the reports queue
+    pub reports: Vec<Report>,
+}
+
+/// A comment. These get run through the same filters as posts
+#[derive(Clone)]
+pub struct Comment {
+    pub id: SmallString,
+    /// ID of the post that the comment is on
+    pub post_id: SmallString,
+    pub author: SmallString,
+    pub score: f64,
+    pub body: String,
+    pub created: OffsetDateTime,
+    pub removed: bool,
+    pub subreddit: SmallString,
+}
+
+/// A reason that users reported a post for along with how many times they did
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub reason: String,
+    pub count: u32,
+}
+
+/// Where in a post a token came from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Origin {
+    Title,
+    Body,
+    Link,
 }
 
 #[derive(Debug)]
 pub enum Token {
     Code { lang: Option<Lang>, text: String },
     Url { text: Option<String>, url: String },
+    Image { alt: String, url: String },
     Text(String),
 }
 
 impl Token {
+    pub fn parse(markdown: &str) -> Vec<Self> {
+        let parser = pulldown_cmark::Parser::new(markdown);
+        // Definitions that never get used don't show up as events, but can still hide links
+        let mut definitions: Vec<_> = parser
+            .reference_definitions()
+            .iter()
+            .map(|(_, definition)| definition.dest.to_string())
+            .collect();
+        definitions.sort();
+        let events: Vec<_> = parser.into_iter().collect();
+
+        let mut tokens = Self::new(events);
+        for url in definitions {
+            let is_seen = tokens.iter().any(|token| match token {
+                Self::Url { url: seen, .. } | Self::Image { url: seen, .. } => *seen == url,
+                _ => false,
+            });
+            if !is_seen {
+                tokens.push(Self::Url { text: None, url });
+            }
+        }
+
+        tokens
+    }
+
     fn new<'text>(events: Vec<pulldown_cmark::Event<'text>>) -> Vec<Self> {
         let mut tokens = Vec::new();
+        // Links and images can be nested like with a badge that links somewhere. Their text gets
+        // collected until they end
+        let mut open_links: Vec<OpenLink> = Vec::new();
 
-        let mut events = events.into_iter();
+        let mut events = events.into_iter().peekable();
         while let Some(event) = events.next() {
             match event {
                 Event::Start(Tag::Heading(_, Some(t), _)) => tokens.push(Token::Text(t.to_owned())),
@@ -45,7 +110,7 @@ impl Token {
 
                     // Munch text until we see the end codeblock
                     let mut text = String::new();
-                    while let Some(event) = events.next() {
+                    for event in events.by_ref() {
                         match event {
                             Event::Text(t) => {
                                 if !text.is_empty() {
@@ -61,38 +126,44 @@ impl Token {
 
                     tokens.push(Token::Code { lang, text });
                 }
-                Event::Start(Tag::Link(LinkType::Inline, url, _)) => {
-                    let url = url.into_string();
-
-                    let mut text = String::new();
-                    while let Some(event) = events.next() {
-                        match event {
-                            Event::Text(t) => {
-                                if !text.is_empty() {
-                                    text.push('\n');
-                                }
-                                text.push_str(&t);
-                            }
-                            Event::End(Tag::Link(LinkType::Inline, _, _)) => break,
-                            // Ignore anything else
-                            _ => {}
-                        }
+                Event::Start(Tag::Link(link_type, url, _)) => open_links.push(OpenLink {
+                    link_type,
+                    url: url.into_string(),
+                    text: String::new(),
+                    is_image: false,
+                }),
+                Event::Start(Tag::Image(link_type, url, _)) => open_links.push(OpenLink {
+                    link_type,
+                    url: url.into_string(),
+                    text: String::new(),
+                    is_image: true,
+                }),
+                Event::End(Tag::Link(..) | Tag::Image(..)) => {
+                    if let Some(link) = open_links.pop() {
+                        tokens.push(link.into_token());
                     }
-                    tokens.push(Token::Url { text: Some(text), url })
                 }
                 Event::Text(t) => {
-                    // TODO: this still misses plaintext urls that look like
-                    // Some content: https://google.com
-                    // Should we run a regex over the line, or is it not worth it?
-                    let token = if t.starts_with("https://") && t.split_whitespace().count() == 1 {
-                        Token::Url { text: None, url: t.into_string() }
-                    } else {
-                        Token::Text(t.into_string())
-                    };
+                    // Text gets split up around anything that could've been markup (like the `_`s
+                    // in a URL), so piece it back together first
+                    let mut text = t.into_string();
+                    while let Some(Event::Text(next)) = events.peek() {
+                        text.push_str(next);
+                        events.next();
+                    }
 
-                    tokens.push(token);
+                    match open_links.last_mut() {
+                        Some(link) => link.text.push_str(&text),
+                        None => tokens.extend(Self::split_urls(&text)),
+                    }
                 }
-                Event::Code(t) => tokens.push(Token::Code { lang: None, text: t.into_string() }),
+                Event::Code(t) => match open_links.last_mut() {
+                    Some(link) => link.text.push_str(&t),
+                    None => tokens.push(Token::Code {
+                        lang: None,
+                        text: t.into_string(),
+                    }),
+                },
                 // Any significant ends should be consumed with their corresponding start
                 Event::End(_) => {}
                 // We don't emit tokens for any of these
@@ -115,19 +186,107 @@ impl Token {
                     | Tag::TableCell
                     | Tag::Emphasis
                     | Tag::Strong
-                    | Tag::Strikethrough
-                    // Inline links are handled above
-                    | Tag::Link(_, _, _)
-                    | Tag::Image(_, _, _),
+                    | Tag::Strikethrough,
                 ) => {}
             }
         }
 
         tokens
     }
+
+    /// Pulls any bare URLs out of the text, leaving the rest as text
+    fn split_urls(text: &str) -> Vec<Self> {
+        static URL_REGEX: OnceLock<Regex> = OnceLock::new();
+        let url_regex =
+            URL_REGEX.get_or_init(|| Regex::new(r"(?i)\b(?:https?://|www\.)[^\s<>]+").unwrap());
+
+        let mut tokens = Vec::new();
+        let mut rest_start = 0;
+        for found in url_regex.find_iter(text) {
+            let url = trim_url_end(found.as_str());
+            // Just the scheme or `www.` isn't much of a URL
+            if !url.contains('.') || url.ends_with("www.") {
+                continue;
+            }
+            let url_end = found.start() + url.len();
+
+            let before = &text[rest_start..found.start()];
+            if !before.is_empty() {
+                tokens.push(Self::Text(before.to_owned()));
+            }
+            // `www.` links work in a browser, but need a scheme to be parsed
+            let url = if url
+                .get(..4)
+                .is_some_and(|start| start.eq_ignore_ascii_case("www."))
+            {
+                format!("https://{url}")
+            } else {
+                url.to_owned()
+            };
+            tokens.push(Self::Url { text: None, url });
+            rest_start = url_end;
+        }
+
+        let rest = &text[rest_start..];
+        if !rest.is_empty() {
+            tokens.push(Self::Text(rest.to_owned()));
+        }
+
+        tokens
+    }
 }
 
-#[derive(Debug)]
+/// A link or image whose text is still being collected
+struct OpenLink {
+    link_type: LinkType,
+    url: String,
+    text: String,
+    is_image: bool,
+}
+
+impl OpenLink {
+    fn into_token(self) -> Token {
+        let url = match self.link_type {
+            LinkType::Email => format!("mailto:{}", self.url),
+            _ => self.url,
+        };
+
+        if self.is_image {
+            Token::Image {
+                alt: self.text,
+                url,
+            }
+        } else {
+            // Autolinks are just the URL, so there's no separate text
+            let is_autolink = matches!(self.link_type, LinkType::Autolink | LinkType::Email);
+            let text = (!is_autolink).then_some(self.text);
+            Token::Url { text, url }
+        }
+    }
+}
+
+/// Drops trailing punctuation that's more likely to be part of the sentence than the URL e.g.
+/// `(see https://example.com).` Closing brackets are kept when they balance out an opening one
+/// within the URL like in `https://en.wikipedia.org/wiki/Rust_(programming_language)`
+fn trim_url_end(mut url: &str) -> &str {
+    while let Some(last) = url.chars().last() {
+        let is_trailing = match last {
+            '.' | ',' | ':' | ';' | '!' | '?' | '\'' | '"' | '*' => true,
+            ')' => url.matches('(').count() < url.matches(')').count(),
+            ']' => url.matches('[').count() < url.matches(']').count(),
+            _ => false,
+        };
+        if !is_trailing {
+            break;
+        }
+
+        url = &url[..url.len() - last.len_utf8()];
+    }
+
+    url
+}
+
+#[derive(Debug, Serialize)]
 pub enum Lang {
     Bash,
     C,
@@ -156,16 +315,78 @@ impl Lang {
 }
 
 impl Post {
+    /// The ID with its type prefix that the reddit API expects
+    pub fn fullname(&self) -> String {
+        format!("t3_{}", self.id)
+    }
+
     pub fn tokens(&self) -> Vec<Token> {
-        self.body
-            .as_deref()
-            .map(|body| {
-                let parser = pulldown_cmark::Parser::new(body);
-                let events: Vec<_> = parser.into_iter().collect();
+        self.body.as_deref().map(Token::parse).unwrap_or_default()
+    }
 
-                Token::new(events)
-            })
-            .unwrap_or_default()
+    /// Tokens from the link, title and body along with where each came from
+    ///
+    /// Titles aren't rendered as markdown, but people still write inline code and links in them
+    pub fn tokens_with_origin(&self) -> Vec<(Origin, Token)> {
+        let link = self.link.iter().map(|url| {
+            let token = Token::Url {
+                text: None,
+                url: url.to_owned(),
+            };
+            (Origin::Link, token)
+        });
+        let title = Token::parse(&self.title)
+            .into_iter()
+            .map(|token| (Origin::Title, token));
+        let body = self.tokens().into_iter().map(|token| (Origin::Body, token));
+
+        link.chain(title).chain(body).collect()
+    }
+}
+
+#[cfg(test)]
+impl Post {
+    /// A text post without a body, for tests to fill in whatever else they care about
+    pub fn for_test(title: &str) -> Self {
+        Self {
+            id: "abc123".into(),
+            author: "someone".into(),
+            score: 1.0,
+            title: title.to_owned(),
+            created: OffsetDateTime::UNIX_EPOCH,
+            body: None,
+            link: None,
+            category: None,
+            removed: false,
+            subreddit: "rust".into(),
+            reports: Vec::new(),
+        }
+    }
+}
+
+impl Comment {
+    /// The ID with its type prefix that the reddit API expects
+    pub fn fullname(&self) -> String {
+        format!("t1_{}", self.id)
+    }
+
+    /// Comments go through the filters as a post without a title or link. The post gets the
+    /// comment's fullname as its ID, so that its verdicts and decisions can't be mixed up with a
+    /// post's
+    pub fn as_post(&self) -> Post {
+        Post {
+            id: self.fullname().into(),
+            author: self.author.clone(),
+            score: self.score,
+            title: String::new(),
+            created: self.created,
+            body: Some(self.body.clone()),
+            link: None,
+            category: None,
+            removed: self.removed,
+            subreddit: self.subreddit.clone(),
+            reports: Vec::new(),
+        }
     }
 }
 
@@ -179,13 +400,13 @@ impl Eq for Post {}
 
 impl PartialOrd for Post {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Post {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.id.cmp(&other.id)
     }
 }
 
@@ -200,6 +421,9 @@ impl fmt::Debug for Post {
             body,
             link,
             category,
+            removed,
+            subreddit,
+            reports,
         } = &self;
 
         let mut debug_struct = f.debug_struct("Post");
@@ -219,14 +443,168 @@ impl fmt::Debug for Post {
 
         debug_struct.field("link", link);
         debug_struct.field("category", category);
+        debug_struct.field("removed", removed);
+        debug_struct.field("subreddit", subreddit);
+        debug_struct.field("reports", reports);
 
         debug_struct.finish()
     }
 }
 
+impl fmt::Debug for Comment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            id,
+            post_id,
+            author,
+            score,
+            body,
+            created,
+            removed,
+            subreddit,
+        } = &self;
+
+        f.debug_struct("Comment")
+            .field("id", id)
+            .field("post_id", post_id)
+            .field("author", author)
+            .field("score", score)
+            .field("body", &utils::truncate_str(body, DEBUG_FIELD_TRUNCATE_LEN))
+            .field("created", &created.format(&Rfc3339).unwrap())
+            .field("removed", removed)
+            .field("subreddit", subreddit)
+            .finish()
+    }
+}
+
 #[derive(DbEnum, Clone, Copy, Debug)]
 pub enum Category {
     Lang,
     Game,
     Other,
 }
+
+/// The coarse outcome of filtering a post
+#[derive(DbEnum, Clone, Copy, Debug, PartialEq)]
+pub enum StatusKind {
+    Spam,
+    Ham,
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(markdown: &str) -> Vec<String> {
+        Token::parse(markdown)
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Url { url, .. } | Token::Image { url, .. } => Some(url),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plain_text_urls() {
+        assert_eq!(urls("https://google.com"), ["https://google.com"]);
+        assert_eq!(
+            urls("Some content: https://google.com. And www.rust-lang.org/learn, too!"),
+            ["https://google.com", "https://www.rust-lang.org/learn"]
+        );
+        // Underscores and the like would otherwise split the text up
+        assert_eq!(
+            urls("Join https://discord.gg/rust_game_server now"),
+            ["https://discord.gg/rust_game_server"]
+        );
+        assert_eq!(
+            urls("(see https://en.wikipedia.org/wiki/Rust_(video_game))"),
+            ["https://en.wikipedia.org/wiki/Rust_(video_game)"]
+        );
+        assert_eq!(urls("(https://example.com/a)?"), ["https://example.com/a"]);
+        assert!(urls("Just https:// or www. on their own").is_empty());
+
+        // The surrounding text is kept
+        let tokens = Token::parse("Check out https://example.com for more");
+        let texts: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, ["Check out ", " for more"]);
+    }
+
+    #[test]
+    fn link_types() {
+        let markdown = "\
+            [inline](https://a.com) [reference][r] [collapsed][] [shortcut] <https://b.com> \
+            <someone@c.com> ![an image](https://d.com/img.png)\n\n\
+            [![badge](https://e.com/badge.svg)](https://f.com)\n\n\
+            [r]: https://g.com\n\
+            [collapsed]: https://h.com\n\
+            [shortcut]: https://i.com\n\
+            [unused]: https://j.com\n";
+        assert_eq!(
+            urls(markdown),
+            [
+                "https://a.com",
+                "https://g.com",
+                "https://h.com",
+                "https://i.com",
+                "https://b.com",
+                "mailto:someone@c.com",
+                "https://d.com/img.png",
+                "https://e.com/badge.svg",
+                "https://f.com",
+                "https://j.com",
+            ]
+        );
+
+        let tokens = Token::parse("![alt *text*](https://d.com/img.png) [`code`](https://a.com)");
+        assert!(
+            matches!(
+                &tokens[..],
+                [Token::Image { alt, .. }, Token::Text(_), Token::Url { text: Some(text), .. }]
+                    if alt == "alt text" && text == "code"
+            ),
+            "{tokens:?}"
+        );
+    }
+
+    #[test]
+    fn token_origins() {
+        let post = Post {
+            body: Some("See [the docs](https://docs.rs)".to_owned()),
+            link: Some("https://github.com".to_owned()),
+            ..Post::for_test("`Vec::new()` is slow? Join https://discord.gg/invite")
+        };
+
+        let origins: Vec<_> = post
+            .tokens_with_origin()
+            .into_iter()
+            .map(|(origin, token)| {
+                let kind = match token {
+                    Token::Code { .. } => "code",
+                    Token::Url { .. } => "url",
+                    Token::Image { .. } => "image",
+                    Token::Text(_) => "text",
+                };
+                (origin, kind)
+            })
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                (Origin::Link, "url"),
+                (Origin::Title, "code"),
+                (Origin::Title, "text"),
+                (Origin::Title, "url"),
+                (Origin::Body, "text"),
+                (Origin::Body, "url"),
+            ]
+        );
+    }
+}